@@ -0,0 +1,146 @@
+
+use serde::{Deserialize, Serialize};
+use super::*;
+
+// chess_engine's Color doesn't implement serde's traits,
+// so this mirrors it for serde to use in its place.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Color")]
+enum ColorDef {
+    White,
+    Black,
+}
+
+// Everything that shapes how an agent plays and learns,
+// apart from the experience itself.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    #[serde(with = "ColorDef")]
+    pub playing_as: Color,
+    pub foresight: i32,
+    pub discount: f32,
+    pub exploration_propensity: f32,
+    pub exploration: Exploration,
+    // Games played in training so far, which
+    // exploration schedules are measured in
+    pub games_played: u32,
+    pub memory_purge_threshold: usize,
+    pub policy: Policy,
+    pub mcts: MctsOptions,
+    pub transposition: TranspositionOptions,
+    pub quiescence: QuiescenceOptions,
+    pub move_ordering: bool,
+    pub update_rule: UpdateRule,
+    pub experience_file: String,
+}
+
+// Written out by hand, since Color doesn't implement Debug
+impl std::fmt::Debug for AgentConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let playing_as = match self.playing_as {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        f.debug_struct("AgentConfig")
+            .field("playing_as", &playing_as)
+            .field("foresight", &self.foresight)
+            .field("discount", &self.discount)
+            .field("exploration_propensity", &self.exploration_propensity)
+            .field("exploration", &self.exploration)
+            .field("games_played", &self.games_played)
+            .field("memory_purge_threshold", &self.memory_purge_threshold)
+            .field("policy", &self.policy)
+            .field("mcts", &self.mcts)
+            .field("transposition", &self.transposition)
+            .field("quiescence", &self.quiescence)
+            .field("move_ordering", &self.move_ordering)
+            .field("update_rule", &self.update_rule)
+            .field("experience_file", &self.experience_file)
+            .finish()
+    }
+}
+
+impl Default for AgentConfig {
+    fn default() -> AgentConfig {
+        ChessAgent::new().config()
+    }
+}
+
+impl ChessAgent {
+    pub fn config(&self) -> AgentConfig {
+        AgentConfig {
+            playing_as: self.playing_as,
+            foresight: self.foresight,
+            discount: self.discount,
+            exploration_propensity: self.exploration_propensity,
+            exploration: self.exploration,
+            games_played: self.games_played,
+            memory_purge_threshold: self.memory_purge_threshold,
+            policy: self.policy,
+            mcts: self.mcts,
+            transposition: self.transposition,
+            quiescence: self.quiescence,
+            move_ordering: self.move_ordering,
+            update_rule: self.experience.update_rule,
+            experience_file: self.experience.long_term_memory_file.clone(),
+        }
+    }
+
+    // Create an agent with the given settings. Its experience
+    // store isn't opened until long_term_recall is called.
+    pub fn from_config(config: &AgentConfig) -> ChessAgent {
+        let mut agent = ChessAgent::new();
+        agent.playing_as = config.playing_as;
+        agent.foresight = config.foresight;
+        agent.discount = config.discount;
+        agent.exploration_propensity = config.exploration_propensity;
+        agent.exploration = config.exploration;
+        agent.games_played = config.games_played;
+        agent.memory_purge_threshold = config.memory_purge_threshold;
+        agent.policy = config.policy;
+        agent.mcts = config.mcts;
+        agent.transposition = config.transposition;
+        agent.quiescence = config.quiescence;
+        agent.move_ordering = config.move_ordering;
+        agent.experience = Experience::new(&config.experience_file, config.memory_purge_threshold);
+        agent.experience.update_rule = config.update_rule;
+        agent
+    }
+}
+
+#[test]
+fn agent_config_test() {
+    let config = AgentConfig {
+        playing_as: Color::Black,
+        foresight: 6,
+        games_played: 120,
+        policy: Policy::MonteCarloTreeSearch,
+        mcts: MctsOptions { simulations: 50, ..MctsOptions::default() },
+        update_rule: UpdateRule::ConstantStepSize(0.1),
+        experience_file: "./other_experience".to_string(),
+        ..AgentConfig::default()
+    };
+
+    let agent = ChessAgent::from_config(&config);
+    assert_eq!(agent.config(), config);
+}
+
+#[test]
+fn agent_config_serialization_test() {
+    let config = AgentConfig {
+        playing_as: Color::Black,
+        discount: 0.75,
+        policy: Policy::AlphaBeta,
+        update_rule: UpdateRule::ExponentialMovingAverage(0.9),
+        exploration: Exploration::EpsilonGreedy(ExplorationSchedule::Linear { start: 1.0, end: 0.1, games: 500 }),
+        ..AgentConfig::default()
+    };
+
+    let ron_text = ron::ser::to_string(&config).unwrap();
+    assert_eq!(ron::de::from_str::<AgentConfig>(&ron_text).unwrap(), config);
+
+    let json_text = serde_json::to_string(&config).unwrap();
+    assert!(json_text.contains("\"playing_as\":\"Black\""));
+    assert_eq!(serde_json::from_str::<AgentConfig>(&json_text).unwrap(), config);
+}
@@ -1,6 +1,12 @@
 
 mod flat;
 
+mod store;
+
+mod update;
+
 mod serialized;
 
 pub use serialized::*;
+pub use store::ExperienceStore;
+pub use update::UpdateRule;
@@ -0,0 +1,244 @@
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use chess_engine::*;
+use crate::environment::*;
+use crate::zobrist::{zobrist_key, CollisionDetector, CollisionStatistics};
+use super::flat::parse_exp_file;
+use super::store::ExperienceStore;
+use super::update::UpdateRule;
+
+pub use super::flat::Recollection;
+
+// Recollections are held in memory as they're made, and
+// written to a single experience store in batches. Positions
+// missing from memory are looked up in the store.
+pub struct Experience {
+    pub long_term_memory_file: String,
+    pub value_map: HashMap<u64, Recollection>,
+    pub memory_purge_threshold: usize,
+    pub update_rule: UpdateRule,
+    store: Option<ExperienceStore>,
+    unsaved: HashSet<u64>,
+    collision_detector: Option<CollisionDetector>,
+}
+
+impl Experience {
+    pub fn new(filename: &str, memory_purge_threshold: usize) -> Experience {
+        Experience {
+            long_term_memory_file: filename.to_string(),
+            value_map: HashMap::new(),
+            memory_purge_threshold,
+            update_rule: UpdateRule::default(),
+            store: None,
+            unsaved: HashSet::new(),
+            collision_detector: None,
+        }
+    }
+
+    pub fn value_of(&self, state: &GameState) -> f32 {
+        match self.recollection_of(state) {
+            Some(recollection) => recollection.average_value,
+            None => 0.0,
+        }
+    }
+
+    // Everything remembered about a position, or None
+    // if it has never been memorized.
+    pub fn recollection_of(&self, state: &GameState) -> Option<Recollection> {
+        self.recall(position_key(state))
+    }
+
+    fn recall(&self, key: u64) -> Option<Recollection> {
+        // Short Term Memory
+        if let Some(recollection) = self.value_map.get(&key) {
+            return Some(*recollection);
+        }
+
+        // Long Term Memory
+        match &self.store {
+            Some(store) => store.get(key).unwrap_or(None),
+            None => None,
+        }
+    }
+
+    pub fn memorize(&mut self, environment: &ChessEnvironment, value: f32) {
+        self.memorize_visits(environment, value, 1);
+    }
+
+    // Memorize a value backed up by several visits, which only
+    // the VisitWeighted update rule gives extra weight to.
+    pub fn memorize_visits(&mut self, environment: &ChessEnvironment, value: f32, visits: i32) {
+        let key = environment.position_key();
+        if let Some(detector) = self.collision_detector.as_mut() {
+            detector.record(key, &environment.state);
+        }
+
+        let recollection = self.recall(key).unwrap_or(Recollection {
+            times_encountered: 0,
+            average_value: 0.0,
+        });
+
+        self.value_map.insert(key, self.update_rule.update(recollection, value, visits));
+        self.unsaved.insert(key);
+    }
+
+    // Start checking memorized positions for zobrist key
+    // collisions. This is slow, and meant for diagnosis.
+    pub fn track_collisions(&mut self) {
+        if self.collision_detector.is_none() {
+            self.collision_detector = Some(CollisionDetector::new());
+        }
+    }
+
+    pub fn collision_statistics(&self) -> Option<CollisionStatistics> {
+        self.collision_detector.as_ref().map(|detector| detector.statistics())
+    }
+
+    // The number of recollections held in memory
+    pub fn len(&self) -> usize {
+        self.value_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value_map.is_empty()
+    }
+
+    // The number of recollections in the experience store
+    pub fn stored_len(&self) -> usize {
+        self.store.as_ref().map_or(0, |store| store.len())
+    }
+
+    // Open the experience store. When the memory file names a
+    // directory, as it did when every position had its own .exp
+    // file, the store is kept inside it and any .exp files are
+    // imported the first time.
+    pub fn long_term_recall(&mut self) -> io::Result<()> {
+        let directory = Path::new(&self.long_term_memory_file);
+        let mut store = ExperienceStore::open(&self.store_path())?;
+        if store.is_empty() && directory.is_dir() {
+            import_exp_files(directory, &mut store)?;
+        }
+        self.store = Some(store);
+        Ok(())
+    }
+
+    // Open the experience store without write access, for experience
+    // that's only played with. A store that was never written to is
+    // left closed, as there's nothing in it to recall.
+    pub fn long_term_recall_read_only(&mut self) -> io::Result<()> {
+        let path = self.store_path();
+        if path.exists() {
+            self.store = Some(ExperienceStore::open_read_only(&path)?);
+        }
+        Ok(())
+    }
+
+    // Forget everything held in memory, unsaved recollections
+    // included, going back to what the store remembers.
+    pub fn forget_short_term(&mut self) {
+        self.value_map.clear();
+        self.unsaved.clear();
+    }
+
+    // The file the experience store is kept in
+    pub fn store_path(&self) -> PathBuf {
+        let directory = Path::new(&self.long_term_memory_file);
+        match directory.is_dir() {
+            true => directory.join("experience.bin"),
+            false => PathBuf::from(&self.long_term_memory_file),
+        }
+    }
+
+    // Write every recollection made since the last call to the
+    // experience store, opening it first if necessary.
+    pub fn long_term_memorize(&mut self) -> io::Result<()> {
+        if self.store.is_none() {
+            self.long_term_recall()?;
+        }
+
+        let mut keys: Vec<u64> = self.unsaved.drain().collect();
+        keys.sort_unstable();
+        let recollections: Vec<(u64, Recollection)> = keys.into_iter()
+            .filter_map(|key| self.value_map.get(&key).map(|recollection| (key, *recollection)))
+            .collect();
+
+        if let Some(store) = self.store.as_mut() {
+            store.put_all(&recollections)?;
+
+            // Reclaim space once most of the file is out of date
+            if store.stale_records() > 2 * store.len() as u64 + 1_000_000 {
+                store.compact()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Forget the least encountered positions until no more than
+    // memory_purge_threshold remain in memory. Unsaved
+    // recollections are written to the store first.
+    pub fn purge_weak_memories(&mut self) -> io::Result<()> {
+        self.long_term_memorize()?;
+
+        let mut keys: Vec<u64> = self.value_map.keys().cloned().collect();
+        keys.sort_by_key(|key| std::cmp::Reverse(self.value_map[key].times_encountered));
+
+        for key in keys.into_iter().skip(self.memory_purge_threshold) {
+            self.value_map.remove(&key);
+        }
+        Ok(())
+    }
+}
+
+// The key a position is stored under
+pub fn position_key(state: &GameState) -> u64 {
+    zobrist_key(state)
+}
+
+// Move recollections from the one-file-per-position layout
+// into a store. The files are left in place.
+fn import_exp_files(directory: &Path, store: &mut ExperienceStore) -> io::Result<()> {
+    let mut recollections = vec![];
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("exp") {
+            continue;
+        }
+
+        let fen = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => stem.replace("|", "/"),
+            None => continue,
+        };
+        let record = match parse_fen(&fen) {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if let Some(recollection) = std::fs::read_to_string(&path).ok().and_then(|text| parse_exp_file(&text)) {
+            recollections.push((position_key(&record.state), recollection));
+        }
+    }
+
+    store.put_all(&recollections)
+}
+
+#[test]
+fn memorize_test() {
+    let mut experience = Experience::new("./experience_memorize_test", 100);
+    let environment = ChessEnvironment::new();
+    assert!(experience.recollection_of(&environment.state).is_none());
+
+    // Neutral values are remembered too
+    experience.memorize(&environment, 0.0);
+    let recollection = experience.recollection_of(&environment.state).unwrap();
+    assert_eq!(recollection.times_encountered, 1);
+    assert_eq!(recollection.average_value, 0.0);
+
+    experience.memorize(&environment, 1.0);
+    experience.memorize(&environment, 0.5);
+    assert!((experience.value_of(&environment.state) - 0.5).abs() < 1e-6);
+
+    experience.update_rule = UpdateRule::VisitWeighted;
+    experience.memorize_visits(&environment, 1.0, 3);
+    assert!((experience.value_of(&environment.state) - 0.75).abs() < 1e-6);
+}
@@ -0,0 +1,238 @@
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use super::flat::Recollection;
+
+// The last byte is the format version. Version 2 keys
+// positions by their zobrist key.
+const MAGIC: &[u8; 8] = b"RLCEXP\x00\x02";
+const HEADER_LENGTH: u64 = 8;
+
+// Each record is a position key, followed by the
+// recollection's times_encountered and average_value,
+// all little endian.
+const RECORD_LENGTH: u64 = 16;
+
+// An append-only file of fixed length records. Updating a
+// position appends a new record, and the index points at
+// the latest record for every key, so a lookup is a single
+// read no matter how many positions are stored.
+pub struct ExperienceStore {
+    path: PathBuf,
+    file: File,
+    index: HashMap<u64, u64>,
+    records_written: u64,
+}
+
+impl ExperienceStore {
+    // Open a store, creating it if it doesn't exist, and
+    // rebuild the index by scanning every record.
+    pub fn open(path: &Path) -> io::Result<ExperienceStore> {
+        ExperienceStore::open_with(path, true)
+    }
+
+    // Open an existing store without write access, for
+    // experience that must never change. Writes to it fail.
+    pub fn open_read_only(path: &Path) -> io::Result<ExperienceStore> {
+        ExperienceStore::open_with(path, false)
+    }
+
+    fn open_with(path: &Path, writable: bool) -> io::Result<ExperienceStore> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .create(writable)
+            .truncate(false)
+            .open(path)?;
+
+        let length = file.metadata()?.len();
+        if length == 0 {
+            if writable {
+                file.write_all(MAGIC)?;
+            }
+        } else {
+            let mut magic = [0; 8];
+            file.read_exact(&mut magic)?;
+            if magic[..7] != MAGIC[..7] {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not an experience store", path.display()),
+                ));
+            }
+            if magic[7] != MAGIC[7] {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} uses experience store version {}, expected {}", path.display(), magic[7], MAGIC[7]),
+                ));
+            }
+        }
+
+        // A record cut short by an interrupted write is discarded
+        let records = length.saturating_sub(HEADER_LENGTH) / RECORD_LENGTH;
+        if writable && length > HEADER_LENGTH + records * RECORD_LENGTH {
+            file.set_len(HEADER_LENGTH + records * RECORD_LENGTH)?;
+        }
+
+        let mut index = HashMap::new();
+        let mut reader = io::BufReader::new(&file);
+        reader.seek(SeekFrom::Start(HEADER_LENGTH))?;
+        let mut record = [0; RECORD_LENGTH as usize];
+        for position in 0..records {
+            reader.read_exact(&mut record)?;
+            let (key, _) = decode_record(&record);
+            index.insert(key, HEADER_LENGTH + position * RECORD_LENGTH);
+        }
+
+        Ok(ExperienceStore {
+            path: path.to_path_buf(),
+            file,
+            index,
+            records_written: records,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.index.contains_key(&key)
+    }
+
+    pub fn get(&self, key: u64) -> io::Result<Option<Recollection>> {
+        let offset = match self.index.get(&key) {
+            Some(offset) => *offset,
+            None => return Ok(None),
+        };
+
+        let mut file = &self.file;
+        let mut record = [0; RECORD_LENGTH as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut record)?;
+        Ok(Some(decode_record(&record).1))
+    }
+
+    pub fn put(&mut self, key: u64, recollection: &Recollection) -> io::Result<()> {
+        self.put_all(&[(key, *recollection)])
+    }
+
+    // Append many records with a single write
+    pub fn put_all(&mut self, recollections: &[(u64, Recollection)]) -> io::Result<()> {
+        let mut buffer = Vec::with_capacity(recollections.len() * RECORD_LENGTH as usize);
+        for (key, recollection) in recollections {
+            buffer.extend_from_slice(&encode_record(*key, recollection));
+        }
+
+        let start = HEADER_LENGTH + self.records_written * RECORD_LENGTH;
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.write_all(&buffer)?;
+
+        for (position, (key, _)) in recollections.iter().enumerate() {
+            self.index.insert(*key, start + position as u64 * RECORD_LENGTH);
+        }
+        self.records_written += recollections.len() as u64;
+        Ok(())
+    }
+
+    // Visit the latest recollection of every stored position,
+    // reading the file front to back.
+    pub fn iter(&self) -> io::Result<impl Iterator<Item = (u64, Recollection)>> {
+        let mut offsets: Vec<u64> = self.index.values().cloned().collect();
+        offsets.sort_unstable();
+
+        let mut recollections = Vec::with_capacity(offsets.len());
+        let mut reader = io::BufReader::new(&self.file);
+        let mut record = [0; RECORD_LENGTH as usize];
+        for offset in offsets {
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut record)?;
+            recollections.push(decode_record(&record));
+        }
+        Ok(recollections.into_iter())
+    }
+
+    // Rewrite the file without superseded records
+    pub fn compact(&mut self) -> io::Result<()> {
+        let recollections: Vec<(u64, Recollection)> = self.iter()?.collect();
+
+        let mut compacted_path = self.path.clone().into_os_string();
+        compacted_path.push(".compacting");
+        let compacted_path = PathBuf::from(compacted_path);
+
+        {
+            let mut compacted = io::BufWriter::new(File::create(&compacted_path)?);
+            compacted.write_all(MAGIC)?;
+            for (key, recollection) in recollections.iter() {
+                compacted.write_all(&encode_record(*key, recollection))?;
+            }
+            compacted.flush()?;
+        }
+
+        std::fs::rename(&compacted_path, &self.path)?;
+        *self = ExperienceStore::open(&self.path)?;
+        Ok(())
+    }
+
+    // Records superseded by later writes, which compaction reclaims
+    pub fn stale_records(&self) -> u64 {
+        self.records_written - self.index.len() as u64
+    }
+}
+
+fn encode_record(key: u64, recollection: &Recollection) -> [u8; RECORD_LENGTH as usize] {
+    let mut record = [0; RECORD_LENGTH as usize];
+    record[0..8].copy_from_slice(&key.to_le_bytes());
+    record[8..12].copy_from_slice(&recollection.times_encountered.to_le_bytes());
+    record[12..16].copy_from_slice(&recollection.average_value.to_le_bytes());
+    record
+}
+
+fn decode_record(record: &[u8; RECORD_LENGTH as usize]) -> (u64, Recollection) {
+    let mut key = [0; 8];
+    let mut times_encountered = [0; 4];
+    let mut average_value = [0; 4];
+    key.copy_from_slice(&record[0..8]);
+    times_encountered.copy_from_slice(&record[8..12]);
+    average_value.copy_from_slice(&record[12..16]);
+
+    let recollection = Recollection {
+        times_encountered: i32::from_le_bytes(times_encountered),
+        average_value: f32::from_le_bytes(average_value),
+    };
+    (u64::from_le_bytes(key), recollection)
+}
+
+#[test]
+fn experience_store_test() {
+    let path = std::env::temp_dir().join(format!("experience_store_test_{}.bin", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let recollection = |times_encountered, average_value| Recollection { times_encountered, average_value };
+
+    let mut store = ExperienceStore::open(&path).unwrap();
+    store.put(1, &recollection(1, 0.5)).unwrap();
+    store.put_all(&[(2, recollection(3, -0.25)), (1, recollection(2, 0.75))]).unwrap();
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.stale_records(), 1);
+    assert_eq!(store.get(1).unwrap().unwrap().average_value, 0.75);
+    assert!(store.get(3).unwrap().is_none());
+
+    // The index is rebuilt from the file on reopening
+    let mut store = ExperienceStore::open(&path).unwrap();
+    assert_eq!(store.get(2).unwrap().unwrap().times_encountered, 3);
+    assert_eq!(store.get(1).unwrap().unwrap().times_encountered, 2);
+
+    store.compact().unwrap();
+    assert_eq!(store.stale_records(), 0);
+    let mut keys: Vec<u64> = store.iter().unwrap().map(|(key, _)| key).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, vec![1, 2]);
+
+    std::fs::remove_file(&path).unwrap();
+}
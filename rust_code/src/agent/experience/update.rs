@@ -0,0 +1,116 @@
+
+use serde::{Deserialize, Serialize};
+use super::flat::Recollection;
+
+// How a recollection's value moves toward each new value memorized
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum UpdateRule {
+    // The mean of every value memorized, each counted once
+    #[default]
+    SampleAverage,
+
+    // Step a fixed fraction alpha of the way toward each new
+    // value, starting from zero. Recent values count for more,
+    // which suits values that change as the agent improves.
+    ConstantStepSize(f32),
+
+    // Keep a fraction decay of the current value and take the
+    // rest from the new value, starting from the first value.
+    ExponentialMovingAverage(f32),
+
+    // The mean of every value memorized, each counted as many
+    // times as the visits that back it up, such as the number
+    // of simulations behind a tree search value.
+    VisitWeighted,
+}
+
+impl UpdateRule {
+    pub fn update(&self, recollection: Recollection, value: f32, visits: i32) -> Recollection {
+        let times_encountered = recollection.times_encountered;
+        let average_value = recollection.average_value;
+
+        match *self {
+            UpdateRule::SampleAverage => Recollection {
+                times_encountered: times_encountered + 1,
+                average_value: average_value + (value - average_value) / (times_encountered + 1) as f32,
+            },
+            UpdateRule::ConstantStepSize(alpha) => Recollection {
+                times_encountered: times_encountered + 1,
+                average_value: average_value + alpha * (value - average_value),
+            },
+            UpdateRule::ExponentialMovingAverage(decay) => Recollection {
+                times_encountered: times_encountered + 1,
+                average_value: match times_encountered {
+                    0 => value,
+                    _ => decay * average_value + (1.0 - decay) * value,
+                },
+            },
+            UpdateRule::VisitWeighted => {
+                let visits = std::cmp::max(1, visits);
+                Recollection {
+                    times_encountered: times_encountered + visits,
+                    average_value: average_value
+                        + (value - average_value) * visits as f32 / (times_encountered + visits) as f32,
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+fn apply_all(rule: UpdateRule, values: &[(f32, i32)]) -> Recollection {
+    let unseen = Recollection { times_encountered: 0, average_value: 0.0 };
+    values.iter().fold(unseen, |recollection, (value, visits)| rule.update(recollection, *value, *visits))
+}
+
+#[test]
+fn sample_average_test() {
+    let recollection = apply_all(UpdateRule::SampleAverage, &[(1.0, 1), (2.0, 1), (3.0, 1), (4.0, 1)]);
+    assert_eq!(recollection.times_encountered, 4);
+    assert!((recollection.average_value - 2.5).abs() < 1e-6);
+
+    // Visits are ignored, and the mean of a long
+    // alternating sequence settles on its midpoint
+    let values: Vec<(f32, i32)> = (0..10_000).map(|i| (if i % 2 == 0 { 1.0 } else { -0.5 }, 7)).collect();
+    let recollection = apply_all(UpdateRule::SampleAverage, &values);
+    assert_eq!(recollection.times_encountered, 10_000);
+    assert!((recollection.average_value - 0.25).abs() < 1e-3);
+}
+
+#[test]
+fn constant_step_size_test() {
+    let values = vec![(1.0, 1); 50];
+    let recollection = apply_all(UpdateRule::ConstantStepSize(0.2), &values);
+    assert!((recollection.average_value - (1.0 - 0.8f32.powi(50))).abs() < 1e-5);
+
+    // After the target changes, the value follows it
+    let mut values = vec![(1.0, 1); 50];
+    values.extend(vec![(-1.0, 1); 100]);
+    let recollection = apply_all(UpdateRule::ConstantStepSize(0.2), &values);
+    assert!((recollection.average_value + 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn exponential_moving_average_test() {
+    let recollection = apply_all(UpdateRule::ExponentialMovingAverage(0.9), &[(0.5, 1); 3]);
+    assert!((recollection.average_value - 0.5).abs() < 1e-6);
+
+    let recollection = apply_all(UpdateRule::ExponentialMovingAverage(0.5), &[(1.0, 1), (0.0, 1), (0.0, 1)]);
+    assert!((recollection.average_value - 0.25).abs() < 1e-6);
+
+    let mut values = vec![(0.0, 1); 10];
+    values.extend(vec![(1.0, 1); 200]);
+    let recollection = apply_all(UpdateRule::ExponentialMovingAverage(0.9), &values);
+    assert!((recollection.average_value - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn visit_weighted_test() {
+    let recollection = apply_all(UpdateRule::VisitWeighted, &[(1.0, 3), (0.0, 1)]);
+    assert_eq!(recollection.times_encountered, 4);
+    assert!((recollection.average_value - 0.75).abs() < 1e-6);
+
+    // Equal to the sample average of every visit
+    let recollection = apply_all(UpdateRule::VisitWeighted, &[(0.2, 10), (-0.4, 30), (1.0, 60)]);
+    assert!((recollection.average_value - (2.0 - 12.0 + 60.0) / 100.0).abs() < 1e-5);
+}
@@ -0,0 +1,253 @@
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use super::ChessAgent;
+
+// A value that changes as training goes on, by the
+// number of games the agent has played.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ExplorationSchedule {
+    Constant(f32),
+
+    // From start to end over the given number
+    // of games, and end from then on
+    Linear { start: f32, end: f32, games: u32 },
+
+    // Multiplied by decay after every game,
+    // but never falling below the minimum
+    Exponential { start: f32, decay: f32, minimum: f32 },
+}
+
+impl ExplorationSchedule {
+    pub fn value(&self, games_played: u32) -> f32 {
+        match *self {
+            ExplorationSchedule::Constant(value) => value,
+            ExplorationSchedule::Linear { start, end, games } => {
+                let progress = match games {
+                    0 => 1.0,
+                    games => (games_played as f32 / games as f32).min(1.0),
+                };
+                start + (end - start) * progress
+            },
+            ExplorationSchedule::Exponential { start, decay, minimum } => {
+                (start * decay.powi(games_played as i32)).max(minimum)
+            },
+        }
+    }
+
+    // The value the schedule settles at once training is over
+    pub fn final_value(&self) -> f32 {
+        match *self {
+            ExplorationSchedule::Constant(value) => value,
+            ExplorationSchedule::Linear { end, .. } => end,
+            ExplorationSchedule::Exponential { start, decay, minimum } => match decay < 1.0 {
+                true => minimum,
+                false => start.max(minimum),
+            },
+        }
+    }
+
+    // Describe any setting that would make the schedule unusable
+    pub fn validate(&self) -> Result<(), String> {
+        let (lowest, decay) = match *self {
+            ExplorationSchedule::Constant(value) => (value, 1.0),
+            ExplorationSchedule::Linear { start, end, .. } => (start.min(end), 1.0),
+            ExplorationSchedule::Exponential { start, decay, minimum } => (start.min(minimum), decay),
+        };
+        if lowest.is_nan() || lowest < 0.0 {
+            return Err(format!("schedule values must not be negative, found {}", lowest));
+        }
+        if !(decay > 0.0 && decay <= 1.0) {
+            return Err(format!("schedule decay must be in (0, 1], found {}", decay));
+        }
+        Ok(())
+    }
+}
+
+// How the agent chooses which line to follow
+// when evaluating a position.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Exploration {
+    // Follow a random line whenever a uniform draw
+    // exceeds the agent's exploration_propensity
+    #[default]
+    Propensity,
+
+    // Follow a random line with the scheduled probability,
+    // and the best known line otherwise
+    EpsilonGreedy(ExplorationSchedule),
+
+    // Sample lines in proportion to exp(value / temperature),
+    // with the temperature following the schedule
+    Boltzmann(ExplorationSchedule),
+
+    // Follow the line with the highest value plus a bonus for
+    // positions seldom encountered, scaled by this constant
+    Ucb(f32),
+}
+
+impl Exploration {
+    // Each strategy by name, with a typical schedule
+    pub fn from_name(name: &str) -> Option<Exploration> {
+        let decaying = ExplorationSchedule::Exponential { start: 1.0, decay: 0.995, minimum: 0.05 };
+        match name.to_lowercase().replace(['_', '-', ' '], "").as_str() {
+            "propensity" => Some(Exploration::Propensity),
+            "epsilonlinear" => Some(Exploration::EpsilonGreedy(ExplorationSchedule::Linear {
+                start: 1.0,
+                end: 0.05,
+                games: 1000,
+            })),
+            "epsilonexponential" | "epsilongreedy" => Some(Exploration::EpsilonGreedy(decaying)),
+            "boltzmann" | "softmax" => Some(Exploration::Boltzmann(decaying)),
+            "ucb" => Some(Exploration::Ucb(1.0)),
+            _ => None,
+        }
+    }
+
+    // The same strategy with its schedule held at the final value,
+    // for judging how the agent plays once training is over.
+    pub fn settled(&self) -> Exploration {
+        match *self {
+            Exploration::EpsilonGreedy(schedule) => {
+                Exploration::EpsilonGreedy(ExplorationSchedule::Constant(schedule.final_value()))
+            },
+            Exploration::Boltzmann(schedule) => {
+                Exploration::Boltzmann(ExplorationSchedule::Constant(schedule.final_value()))
+            },
+            exploration => exploration,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Exploration::Propensity => Ok(()),
+            Exploration::EpsilonGreedy(schedule) | Exploration::Boltzmann(schedule) => schedule.validate(),
+            Exploration::Ucb(constant) if constant.is_nan() || *constant < 0.0 => {
+                Err(format!("ucb constant must not be negative, found {}", constant))
+            },
+            Exploration::Ucb(_) => Ok(()),
+        }
+    }
+}
+
+impl ChessAgent {
+    // Choose the index of the decision whose line to follow,
+    // according to the agent's exploration strategy.
+    pub fn choose_line(&mut self, decisions: &mut Vec<GameState>) -> usize {
+        match self.exploration {
+            Exploration::Propensity => match self.will_explore() {
+                true => self.rng.gen_range(0, decisions.len()),
+                false => {
+                    self.rank_confidence_in_positions(decisions);
+                    0
+                },
+            },
+            Exploration::EpsilonGreedy(schedule) => {
+                match self.rng.gen::<f32>() < schedule.value(self.games_played) {
+                    true => self.rng.gen_range(0, decisions.len()),
+                    false => self.best_line(decisions, |_| 0.0),
+                }
+            },
+            Exploration::Boltzmann(schedule) => {
+                let temperature = schedule.value(self.games_played).max(f32::EPSILON);
+                self.boltzmann_line(decisions, temperature)
+            },
+            Exploration::Ucb(constant) => {
+                let encounters: Vec<f32> = decisions.iter().map(|decision| {
+                    self.experience.recollection_of(decision)
+                        .map_or(0.0, |recollection| recollection.times_encountered as f32)
+                }).collect();
+                let total: f32 = encounters.iter().sum();
+                self.best_line(decisions, |index| {
+                    constant * ((total + 1.0).ln() / (encounters[index] + 1.0)).sqrt()
+                })
+            },
+        }
+    }
+
+    // The decision with the highest experienced value for
+    // the agent, after adding the given bonus to each.
+    fn best_line<F: Fn(usize) -> f32>(&self, decisions: &[GameState], bonus: F) -> usize {
+        let mut best_index = 0;
+        let mut best_score = f32::NEG_INFINITY;
+        for (index, decision) in decisions.iter().enumerate() {
+            let score = self.value_from_own_perspective(self.experience.value_of(decision)) + bonus(index);
+            if score > best_score {
+                best_index = index;
+                best_score = score;
+            }
+        }
+        best_index
+    }
+
+    fn boltzmann_line(&mut self, decisions: &[GameState], temperature: f32) -> usize {
+        let values: Vec<f32> = decisions.iter()
+            .map(|decision| self.value_from_own_perspective(self.experience.value_of(decision)))
+            .collect();
+
+        // Shifting by the highest value keeps exp from overflowing
+        // at low temperatures, without changing the probabilities.
+        let highest = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = values.iter().map(|value| ((value - highest) / temperature).exp()).collect();
+        let total: f32 = weights.iter().sum();
+
+        let mut remaining = self.rng.gen::<f32>() * total;
+        for (index, weight) in weights.iter().enumerate() {
+            remaining -= weight;
+            if remaining <= 0.0 {
+                return index;
+            }
+        }
+        decisions.len() - 1
+    }
+}
+
+#[test]
+fn exploration_schedule_test() {
+    let linear = ExplorationSchedule::Linear { start: 1.0, end: 0.2, games: 4 };
+    assert_eq!(linear.value(0), 1.0);
+    assert!((linear.value(2) - 0.6).abs() < 0.0001);
+    assert!((linear.value(10) - 0.2).abs() < 0.0001);
+
+    let exponential = ExplorationSchedule::Exponential { start: 1.0, decay: 0.5, minimum: 0.1 };
+    assert_eq!(exponential.value(2), 0.25);
+    assert_eq!(exponential.value(10), 0.1);
+    assert_eq!(exponential.final_value(), 0.1);
+    assert_eq!(
+        Exploration::EpsilonGreedy(linear).settled(),
+        Exploration::EpsilonGreedy(ExplorationSchedule::Constant(0.2)),
+    );
+    assert!(ExplorationSchedule::Exponential { start: 1.0, decay: 1.5, minimum: 0.0 }.validate().is_err());
+
+    assert_eq!(Exploration::from_name("UCB"), Some(Exploration::Ucb(1.0)));
+    assert!(Exploration::Ucb(-1.0).validate().is_err());
+}
+
+#[test]
+fn choose_line_test() {
+    use crate::environment::ChessEnvironment;
+
+    let environment = ChessEnvironment::new();
+    let mut decisions = environment.available_decisions();
+    let mut agent = ChessAgent::new();
+    agent.playing_as = Color::White;
+    agent.experience.memorize(&environment.branch(decisions[3]), 0.5);
+    for _ in 0..50 {
+        agent.experience.memorize(&environment.branch(decisions[5]), 0.6);
+    }
+
+    // Without exploration the best known line is followed
+    agent.exploration = Exploration::EpsilonGreedy(ExplorationSchedule::Constant(0.0));
+    assert_eq!(agent.choose_line(&mut decisions), 5);
+
+    // A near zero temperature is the same as no exploration
+    agent.exploration = Exploration::Boltzmann(ExplorationSchedule::Constant(0.001));
+    assert_eq!(agent.choose_line(&mut decisions), 5);
+
+    // A large enough bonus favours the line seen least
+    agent.exploration = Exploration::Ucb(1.0);
+    assert_eq!(agent.choose_line(&mut decisions), 0);
+    agent.exploration = Exploration::Ucb(0.01);
+    assert_eq!(agent.choose_line(&mut decisions), 5);
+}
@@ -5,10 +5,24 @@ static DEBUG: bool = false;
 use rand::Rng;
 use chess_engine::*;
 use crate::environment::*;
-use std::collections::HashMap;
+use crate::random::SeededRng;
 
 mod experience;
 use experience::Experience;
+pub use experience::{ExperienceStore, Recollection, UpdateRule};
+
+mod search;
+pub use search::{Policy, MctsOptions, VisitDistribution};
+pub use search::{Clock, SearchInfo, SearchLimits, MAX_SEARCH_DEPTH};
+pub use search::{Bound, TableStatistics, TranspositionEntry, TranspositionOptions, TranspositionTable};
+pub use search::{MoveOrdering, QuiescenceOptions};
+use search::SearchBudget;
+
+mod config;
+pub use config::AgentConfig;
+
+mod exploration;
+pub use exploration::{Exploration, ExplorationSchedule};
 
 pub struct ChessAgent {
     pub playing_as: Color,
@@ -16,38 +30,75 @@ pub struct ChessAgent {
     pub last_decision: GameState,
     pub foresight: i32,
     pub discount: f32,
-    pub positions_evaluated: i32,
+    pub positions_evaluated: u64,
     pub exploration_propensity: f32,
+    pub exploration: Exploration,
+    // Games played in training so far, which
+    // exploration schedules are measured in
+    pub games_played: u32,
     pub memory_purge_threshold: usize,
+    pub policy: Policy,
+    pub mcts: MctsOptions,
+    pub last_visit_distribution: VisitDistribution,
+    pub last_search: SearchInfo,
+    pub transposition: TranspositionOptions,
+    pub quiescence: QuiescenceOptions,
+    // Search the most promising decisions first
+    pub move_ordering: bool,
+    pub ordering: MoveOrdering,
+    pub transposition_table: TranspositionTable,
+    // The limits of the search in progress
+    budget: SearchBudget,
+    // Every random choice the agent makes is drawn from here
+    pub rng: SeededRng,
 }
 
 impl ChessAgent {
     pub fn new() -> ChessAgent {
         ChessAgent {
             playing_as: Color::White,
-            experience: Experience {
-                long_term_memory_file: "./experience".to_string(),
-                value_map: HashMap::new(),
-                memory_purge_threshold: 100_000,
-            },
+            experience: Experience::new("./experience", 100_000),
             last_decision: GameState::new(),
             foresight: 4,
             discount: 0.9,
             positions_evaluated: 0,
             exploration_propensity: 0.5,
+            exploration: Exploration::Propensity,
+            games_played: 0,
             memory_purge_threshold: 100_000,
+            policy: Policy::SampledLine,
+            mcts: MctsOptions::default(),
+            last_visit_distribution: vec![],
+            last_search: SearchInfo::default(),
+            transposition: TranspositionOptions::default(),
+            quiescence: QuiescenceOptions::default(),
+            move_ordering: true,
+            ordering: MoveOrdering::default(),
+            transposition_table: TranspositionTable::default(),
+            budget: SearchBudget::default(),
+            rng: SeededRng::unseeded(),
         }
     }
 
-    pub fn will_explore(&self) -> bool {
+    // Forget the current experience, and draw on the
+    // experience stored somewhere else instead.
+    pub fn use_experience_from(&mut self, filename: &str) -> std::io::Result<()> {
+        let mut experience = Experience::new(filename, self.memory_purge_threshold);
+        experience.long_term_recall()?;
+        self.experience = experience;
+        Ok(())
+    }
+
+    pub fn will_explore(&mut self) -> bool {
         // Generate a random float between 0 and 1,
         // returning true if it's higher than the  agent's
         // propensity to explore, and false otherwise.
-        let mut rng = rand::thread_rng();
-        let random_float = rng.gen::<f32>();
+        let random_float = self.rng.gen::<f32>();
         random_float - (random_float as i32) as f32 > self.exploration_propensity
     }
 
+    // The sort is stable, so positions the agent knows equally
+    // little about keep the order move ordering gave them.
     pub fn rank_confidence_in_positions(&self, positions: &mut Vec<GameState>) {
         positions.sort_by(|a, b| {
             let value_a = self.experience.value_of(&a);
@@ -62,13 +113,101 @@ impl ChessAgent {
 
     // Policy Function
     pub fn react(&mut self, environment: &ChessEnvironment) -> GameState {
+        self.react_within(environment, &SearchLimits::default())
+    }
+
+    // Choose a decision without going past the given limits. Under
+    // a time or node limit the search deepens one ply at a time,
+    // and the decision from the deepest search to finish is played.
+    pub fn react_within(&mut self, environment: &ChessEnvironment, limits: &SearchLimits) -> GameState {
+        self.budget = SearchBudget::new(limits);
+        self.transposition_table.prepare(&self.transposition);
+        self.ordering.new_search();
+
+        let (best_decision, depth) = match self.policy {
+            Policy::MonteCarloTreeSearch => {
+                // Simulations simply stop when the budget runs out
+                self.budget.enforce(true);
+                let (decision, distribution) = self.monte_carlo_search(environment);
+                self.last_visit_distribution = distribution;
+                (decision, self.foresight)
+            },
+            _ => self.iterative_deepening(environment, limits),
+        };
+
+        self.last_search = self.budget.info(depth);
+        self.budget = SearchBudget::default();
+        self.last_decision = best_decision;
+        best_decision
+    }
+
+    fn iterative_deepening(&mut self, environment: &ChessEnvironment, limits: &SearchLimits) -> (GameState, i32) {
+        let target_depth = std::cmp::max(1, match (limits.depth, self.budget.is_limited()) {
+            (Some(depth), _) => depth,
+            (None, true) => MAX_SEARCH_DEPTH,
+            (None, false) => self.foresight,
+        });
+
+        // Without anything to cut the search short, the
+        // shallower iterations would be wasted work.
+        let first_depth = match self.budget.is_limited() {
+            true => 1,
+            false => target_depth,
+        };
+
+        let foresight = self.foresight;
+        let mut best = None;
+        for depth in first_depth..=target_depth {
+            if best.is_some() && !self.budget.can_deepen() {
+                break;
+            }
+
+            // The first iteration always finishes, so there's
+            // a decision to fall back on.
+            self.budget.enforce(best.is_some());
+            self.foresight = depth;
+            let result = self.search_to_foresight(environment);
+            if self.budget.is_exhausted() {
+                break;
+            }
+            best = Some((result, depth));
+        }
+        self.foresight = foresight;
+
+        let ((decision, value), depth) = best.unwrap();
+        if let Some(value) = value {
+            // Only the chosen line is searched exactly, so it's
+            // the only one worth remembering.
+            let value_for_white = match environment.state.to_move {
+                Color::White => value,
+                Color::Black => -value,
+            };
+            self.experience.memorize(&environment.branch(decision), value_for_white);
+        }
+        (decision, depth)
+    }
+
+    // One complete search to the agent's foresight, along with
+    // the decision's value for the side to move, when the
+    // search finds it exactly.
+    fn search_to_foresight(&mut self, environment: &ChessEnvironment) -> (GameState, Option<f32>) {
+        match self.policy {
+            Policy::AlphaBeta => {
+                let (decision, value) = self.alpha_beta_search(environment);
+                (decision, Some(value))
+            },
+            _ => (self.sampled_line_decision(environment), None),
+        }
+    }
+
+    fn sampled_line_decision(&mut self, environment: &ChessEnvironment) -> GameState {
         let decisions = environment.available_decisions();
 
         let mut best_decision = decisions[0];
         let mut best_value: f32 = -1.0;
         
         for decision in decisions.iter() {
-            let next_environment = ChessEnvironment { state: *decision };
+            let next_environment = environment.branch(*decision);
             let value_for_white = self.evaluate(&next_environment, 1);
             let value_for_self = self.value_from_own_perspective(value_for_white);
 
@@ -77,8 +216,7 @@ impl ChessAgent {
                 best_value = value_for_self;
             }
         }
-        
-        self.last_decision = best_decision;
+
         best_decision
     }
 
@@ -86,24 +224,33 @@ impl ChessAgent {
     pub fn evaluate(&mut self, environment: &ChessEnvironment, depth: i32) -> f32 {
         let (white_score, black_score) = relative_material_values(&environment.state);
 
+        // The search is being abandoned, so the value doesn't matter
+        if self.budget.spend() {
+            return 0.0;
+        }
+
         // Recursion base case
         if environment.is_terminated() {
-            let value = match environment.terminal_state(self.playing_as) {
-                TerminalState::Win => 1.0,
-                TerminalState::Loss => -1.0,
-                TerminalState::Draw => 0.0,
-            };
+            let value = environment.terminal_state(self.playing_as).reward();
 
             // Apply discount function
             return value * self.discount.powf(depth as f32)
         }
 
+        // Recursion base case
+        if depth == self.foresight && self.quiescence.enabled {
+            let value_for_mover = self.quiescence(environment, depth, f32::NEG_INFINITY, f32::INFINITY, 0);
+            return match environment.state.to_move {
+                Color::White => value_for_mover,
+                Color::Black => -value_for_mover,
+            };
+        }
+
         // Recursion base case
         if depth == self.foresight {
             // Calculate expected value based on material, normalizing
             // between -1.0 and 1.0.
-            let max_score = std::cmp::max(white_score, black_score) as f32;
-            let value = (white_score as f32 - black_score as f32) / max_score;
+            let value = material_balance(&environment.state, Color::White);
 
             // Values are discounted based on their distance into the future.
             // This accounts for uncertainty, and the represents the idea that
@@ -114,24 +261,12 @@ impl ChessAgent {
         }
 
         let mut decisions = environment.available_decisions();
-        let mut decision_index_to_evaluate = 0;
-
+        self.order_decisions(environment, depth, &mut decisions, None);
 
         // Choose between exploration / exploitation
-        if self.will_explore() {
-            // Chose a random next position to explore
-            let mut rng = rand::thread_rng();
-            decision_index_to_evaluate = rng.gen_range(0, decisions.len());
-        }
-
-        else {
-            // Exploit the position that's most familiar / confident
-            self.rank_confidence_in_positions(&mut decisions);
-        }
+        let decision_index_to_evaluate = self.choose_line(&mut decisions);
 
-        let next_state = ChessEnvironment {
-            state: decisions[decision_index_to_evaluate],
-        };
+        let next_state = environment.branch(decisions[decision_index_to_evaluate]);
 
         // The value of the current state, discounting for distance into the future
         let expected_value = self.experience.value_of(&environment.state);
@@ -153,10 +288,50 @@ impl ChessAgent {
             println!("recursive_value: {}\n", value);
         }
 
-        self.experience.memorize(&environment, value);
+        // Values from an abandoned search are incomplete
+        if !self.budget.is_exhausted() {
+            self.experience.memorize(environment, value);
+        }
         value
     }
 
+    // Learn from a finished game by memorizing each of its
+    // positions with the final result, discounted by how
+    // many plies separate the position from the result.
+    pub fn study_game(&mut self, states: &[GameState], value_for_white: f32) {
+        let final_ply = states.len() as i32 - 1;
+        for (ply, state) in states.iter().enumerate() {
+            let distance = final_ply - ply as i32;
+            let value = value_for_white * self.discount.powf(distance as f32);
+            self.experience.memorize(&ChessEnvironment::from_state(*state), value);
+        }
+    }
+
+    // Study a game played in an environment, if it has finished.
+    // Returns false for a game that was cut short, which has no
+    // result to learn from.
+    pub fn study_environment(&mut self, environment: &ChessEnvironment) -> bool {
+        if !environment.is_terminated() {
+            return false;
+        }
+
+        let value_for_white = environment.terminal_state(Color::White).reward();
+        let mut states = vec![environment.initial_state()];
+        states.extend(environment.history().into_iter().map(|ply| ply.state));
+        self.study_game(&states, value_for_white);
+        true
+    }
+
+    // Choose a decision for whichever side is to move,
+    // rather than the side the agent is playing as.
+    pub fn react_as_mover(&mut self, environment: &ChessEnvironment) -> GameState {
+        let playing_as = self.playing_as;
+        self.playing_as = environment.state.to_move;
+        let decision = self.react(environment);
+        self.playing_as = playing_as;
+        decision
+    }
+
     fn value_from_own_perspective(&self, value: f32) -> f32 {
         match self.playing_as {
             Color::White => value,
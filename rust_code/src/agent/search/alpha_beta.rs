@@ -0,0 +1,126 @@
+
+use chess_engine::*;
+use crate::agent::ChessAgent;
+use crate::environment::*;
+use super::Bound;
+
+impl ChessAgent {
+    // Choose the decision with the best negamax value, searching
+    // every line until the agent's foresight runs out. Returns
+    // the decision along with its value for the side to move.
+    pub fn alpha_beta_search(&mut self, environment: &ChessEnvironment) -> (GameState, f32) {
+        let mut decisions = environment.available_decisions();
+        let table_move = self.probe_table(environment, 0).and_then(|entry| entry.best_move);
+        self.order_decisions(environment, 0, &mut decisions, table_move);
+
+        let mut best_decision = decisions[0];
+        let mut best_key = None;
+        let mut alpha = f32::NEG_INFINITY;
+
+        for decision in decisions.iter() {
+            let next_environment = environment.branch(*decision);
+            let value = -self.negamax(&next_environment, 1, f32::NEG_INFINITY, -alpha);
+
+            if value > alpha {
+                best_decision = *decision;
+                best_key = Some(next_environment.position_key());
+                alpha = value;
+            }
+        }
+
+        self.store_in_table(environment, 0, alpha, Bound::Exact, best_key);
+        (best_decision, alpha)
+    }
+
+    // The value of a position for the side to move, assuming
+    // both sides play the best line the search can find.
+    // Lines that fall outside (alpha, beta) can't affect
+    // the decision, so they're abandoned early.
+    pub fn negamax(&mut self, environment: &ChessEnvironment, depth: i32, mut alpha: f32, beta: f32) -> f32 {
+        if depth >= self.foresight && self.quiescence.enabled {
+            return self.quiescence(environment, depth, alpha, beta, 0);
+        }
+
+        self.positions_evaluated += 1;
+        if self.budget.spend() {
+            return 0.0;
+        }
+        let to_move = environment.state.to_move;
+
+        // Nearer results are worth more than distant ones,
+        // which also makes the search prefer faster mates.
+        let discount = self.discount.powf(depth as f32);
+
+        if environment.is_terminated() {
+            return environment.terminal_state(to_move).reward() * discount;
+        }
+
+        if depth >= self.foresight {
+            return self.leaf_value(&environment.state, to_move) * discount;
+        }
+
+        // A deep enough result from another line to the
+        // same position can settle it without a search.
+        let mut table_move = None;
+        if let Some(entry) = self.probe_table(environment, depth) {
+            table_move = entry.best_move;
+            if entry.depth >= self.foresight - depth {
+                let settled = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => entry.value >= beta,
+                    Bound::Upper => entry.value <= alpha,
+                };
+                if settled {
+                    self.transposition_table.record_cutoff();
+                    return entry.value;
+                }
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best_value = f32::NEG_INFINITY;
+        let mut best_key = None;
+        let mut decisions = environment.available_decisions();
+        self.order_decisions(environment, depth, &mut decisions, table_move);
+        for decision in decisions {
+            let next_environment = environment.branch(decision);
+            let value = -self.negamax(&next_environment, depth + 1, -beta, -alpha);
+
+            if value > best_value {
+                best_value = value;
+                best_key = Some(next_environment.position_key());
+            }
+            if value > alpha {
+                alpha = value;
+            }
+            if alpha >= beta {
+                self.ordering.record_cutoff(&environment.state, &decision, depth, self.foresight - depth);
+                break;
+            }
+        }
+
+        let bound = match best_value {
+            value if value <= original_alpha => Bound::Upper,
+            value if value >= beta => Bound::Lower,
+            _ => Bound::Exact,
+        };
+        self.store_in_table(environment, depth, best_value, bound, best_key);
+        best_value
+    }
+}
+
+#[test]
+fn alpha_beta_search_test() {
+    let mut agent = ChessAgent::new();
+    agent.foresight = 2;
+
+    let (environment, mate) = rook_mate_in_one();
+    let (decision, value) = agent.alpha_beta_search(&environment);
+    assert_eq!(decision.to_string(), mate.to_string());
+    assert!(value > 0.0);
+
+    // Taking the undefended queen wins material
+    let environment = ChessEnvironment::from_fen("k7/8/8/3q4/8/8/8/K2R4 w - - 0 1").unwrap();
+    let (decision, _) = agent.alpha_beta_search(&environment);
+    assert!(decision.squares[35].is_some_and(|piece| piece.color == Color::White));
+}
@@ -0,0 +1,228 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// The deepest an unlimited depth search will go, when
+// only time or nodes limit it.
+pub const MAX_SEARCH_DEPTH: i32 = 64;
+
+// The time left on the searching side's clock
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    pub increment: Duration,
+    // Moves until the next time control, or None when
+    // the remaining time must last the rest of the game
+    pub moves_to_go: Option<u32>,
+}
+
+impl Clock {
+    // How long to spend on one move: an even share of the time
+    // left over the moves it must last, plus most of the
+    // increment, always keeping a margin in reserve.
+    pub fn budget(&self) -> Duration {
+        // Games are assumed to last another 30 moves
+        let moves = std::cmp::max(1, self.moves_to_go.unwrap_or(30));
+        let share = self.remaining / moves + self.increment * 3 / 4;
+
+        let margin = std::cmp::max(self.remaining / 20, Duration::from_millis(20));
+        std::cmp::min(share, self.remaining.saturating_sub(margin))
+    }
+}
+
+// Limits on a single search, which ends when the first of them is
+// reached. With no limits at all, the agent searches to its foresight.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub clock: Option<Clock>,
+    // Set from another thread to end the search early
+    pub stop: Option<Arc<AtomicBool>>,
+    // Keep searching until stopped, rather than
+    // to the foresight or simulations configured
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: i32) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits { nodes: Some(nodes), ..SearchLimits::default() }
+    }
+
+    pub fn movetime(movetime: Duration) -> SearchLimits {
+        SearchLimits { movetime: Some(movetime), ..SearchLimits::default() }
+    }
+
+    // The time the search may take, from the movetime or the clock
+    pub fn time_budget(&self) -> Option<Duration> {
+        let clock_budget = self.clock.map(|clock| clock.budget());
+        match (self.movetime, clock_budget) {
+            (Some(movetime), Some(budget)) => Some(std::cmp::min(movetime, budget)),
+            (movetime, budget) => movetime.or(budget),
+        }
+    }
+}
+
+// What the last search did
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SearchInfo {
+    // The deepest search that was completed
+    pub depth: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+// Tracks a search against its limits. Once the budget is
+// exhausted, the search unwinds and its result is discarded.
+#[derive(Debug, Default)]
+pub(crate) struct SearchBudget {
+    started: Option<Instant>,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    stop: Option<Arc<AtomicBool>>,
+    until_stopped: bool,
+    nodes: u64,
+    // Limits are only enforced once there's a complete
+    // result to fall back on
+    enforced: bool,
+    exhausted: bool,
+}
+
+impl SearchBudget {
+    pub(crate) fn new(limits: &SearchLimits) -> SearchBudget {
+        let started = Instant::now();
+        SearchBudget {
+            started: Some(started),
+            deadline: limits.time_budget().map(|budget| started + budget),
+            node_limit: limits.nodes,
+            stop: limits.stop.clone(),
+            until_stopped: limits.infinite && limits.stop.is_some(),
+            ..SearchBudget::default()
+        }
+    }
+
+    // Whether anything other than depth can end the search. Being
+    // able to stop it doesn't count, unless that's all that will.
+    pub(crate) fn is_limited(&self) -> bool {
+        self.deadline.is_some() || self.node_limit.is_some() || self.until_stopped
+    }
+
+    pub(crate) fn enforce(&mut self, enforced: bool) {
+        self.enforced = enforced;
+    }
+
+    // Count a node, returning true once the search must stop
+    pub(crate) fn spend(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.enforced || self.exhausted {
+            return self.exhausted;
+        }
+
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.exhausted = true;
+        }
+
+        // Reading the clock costs more than a node, so
+        // it's only checked every so often.
+        if self.nodes & 63 == 0 {
+            self.exhausted |= self.out_of_time() || self.stopped();
+        }
+        self.exhausted
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    // Each iteration takes longer than the last, so there's no
+    // point starting one after half the time has been used.
+    pub(crate) fn can_deepen(&self) -> bool {
+        if self.exhausted || self.stopped() {
+            return false;
+        }
+        match (self.started, self.deadline) {
+            (Some(started), Some(deadline)) => Instant::now() < started + (deadline - started) / 2,
+            _ => true,
+        }
+    }
+
+    pub(crate) fn info(&self, depth: i32) -> SearchInfo {
+        SearchInfo {
+            depth,
+            nodes: self.nodes,
+            elapsed: self.started.map_or(Duration::default(), |started| started.elapsed()),
+        }
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+}
+
+#[test]
+fn search_limits_test() {
+    let clock = Clock {
+        remaining: Duration::from_secs(60),
+        increment: Duration::from_secs(2),
+        moves_to_go: Some(20),
+    };
+    assert_eq!(clock.budget(), Duration::from_millis(4500));
+
+    // Never more than the time left, less a margin
+    let short = Clock { remaining: Duration::from_millis(100), increment: Duration::from_secs(5), moves_to_go: None };
+    assert_eq!(short.budget(), Duration::from_millis(80));
+
+    let limits = SearchLimits { clock: Some(clock), ..SearchLimits::movetime(Duration::from_secs(1)) };
+    assert_eq!(limits.time_budget(), Some(Duration::from_secs(1)));
+    assert_eq!(SearchLimits::depth(3).time_budget(), None);
+
+    let mut budget = SearchBudget::new(&SearchLimits::nodes(10));
+    assert!(budget.is_limited());
+    assert!(!(0..20).any(|_| budget.spend()));
+    budget.enforce(true);
+    assert!(budget.spend());
+    assert!(!budget.can_deepen());
+
+    let stop = Arc::new(AtomicBool::new(true));
+    let budget = SearchBudget::new(&SearchLimits { stop: Some(stop.clone()), ..SearchLimits::default() });
+    assert!(!budget.is_limited());
+    assert!(!budget.can_deepen());
+    let budget = SearchBudget::new(&SearchLimits { stop: Some(stop), infinite: true, ..SearchLimits::default() });
+    assert!(budget.is_limited());
+}
+
+#[test]
+fn react_within_test() {
+    use crate::agent::{ChessAgent, Policy};
+    use crate::environment::{rook_mate_in_one, ChessEnvironment};
+
+    let mut agent = ChessAgent::new();
+    agent.policy = Policy::AlphaBeta;
+    agent.foresight = 2;
+
+    // The mate is found by the first iteration
+    let (environment, mate) = rook_mate_in_one();
+    let decision = agent.react_within(&environment, &SearchLimits::nodes(5_000));
+    assert_eq!(decision.to_string(), mate.to_string());
+    assert!(agent.last_search.depth >= 1);
+    assert_eq!(agent.foresight, 2);
+
+    // A time limit is kept, even with no depth limit
+    let environment = ChessEnvironment::new();
+    let decision = agent.react_within(&environment, &SearchLimits::movetime(Duration::from_millis(200)));
+    assert!(environment.available_decisions().iter().any(|state| state.to_string() == decision.to_string()));
+    assert!(agent.last_search.elapsed < Duration::from_secs(2));
+
+    // Without limits the agent searches to its foresight
+    agent.react(&environment);
+    assert_eq!(agent.last_search.depth, 2);
+}
@@ -0,0 +1,264 @@
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::ChessAgent;
+use crate::environment::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MctsOptions {
+    pub simulations: u32,
+
+    // How strongly selection favors decisions with a high
+    // prior or few visits over those that have scored well.
+    pub exploration_constant: f32,
+
+    // At 0 the most visited decision is always played. Higher
+    // temperatures choose more evenly between visited decisions.
+    pub temperature: f32,
+
+    // Plies of random play from a new leaf before estimating
+    // its value. At 0 the leaf itself is estimated.
+    pub rollout_depth: i32,
+}
+
+impl Default for MctsOptions {
+    fn default() -> MctsOptions {
+        MctsOptions {
+            simulations: 200,
+            exploration_constant: 1.5,
+            temperature: 0.0,
+            rollout_depth: 0,
+        }
+    }
+}
+
+// The share of the root's visits each decision received,
+// which can be stored as a target for training a policy.
+pub type VisitDistribution = Vec<(GameState, f32)>;
+
+struct Node {
+    environment: ChessEnvironment,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    prior: f32,
+    visits: u32,
+    // The sum of backed up values, from the perspective
+    // of the side that made the move leading here.
+    total_value: f32,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(environment: ChessEnvironment, parent: Option<usize>, prior: f32) -> Node {
+        Node {
+            environment,
+            parent,
+            children: vec![],
+            prior,
+            visits: 0,
+            total_value: 0.0,
+            expanded: false,
+        }
+    }
+
+    fn mean_value(&self) -> f32 {
+        match self.visits {
+            0 => 0.0,
+            visits => self.total_value / visits as f32,
+        }
+    }
+}
+
+impl ChessAgent {
+    // Run the configured number of simulations from the current
+    // position, or as many as the search limits allow, returning
+    // the chosen decision along with the distribution of visits
+    // over every available decision.
+    pub fn monte_carlo_search(&mut self, environment: &ChessEnvironment) -> (GameState, VisitDistribution) {
+        let mut tree = vec![Node::new(environment.clone(), None, 1.0)];
+
+        let limited = self.budget.is_limited();
+        let mut simulations = 0;
+        loop {
+            // Under a search limit, simulations carry on until
+            // it's reached, rather than to a fixed number. Either
+            // way they end early when the search is stopped.
+            let finished = self.budget.is_exhausted()
+                || (!limited && simulations >= std::cmp::max(1, self.mcts.simulations));
+            if finished {
+                break;
+            }
+            self.budget.spend();
+            simulations += 1;
+
+            // Selection
+            let mut index = 0;
+            while tree[index].expanded && !tree[index].children.is_empty() {
+                index = self.select_child(&tree, index);
+            }
+
+            // Expansion, and an estimate of the leaf's value
+            // for the side to move there
+            let leaf = &tree[index].environment;
+            let value = match leaf.is_terminated() {
+                true => leaf.terminal_state(leaf.state.to_move).reward(),
+                false => {
+                    self.expand(&mut tree, index);
+                    self.estimate(&tree[index].environment)
+                },
+            };
+
+            // Backup, flipping perspective at every ply
+            let mut value = -value;
+            let mut cursor = Some(index);
+            while let Some(node) = cursor {
+                tree[node].visits += 1;
+                tree[node].total_value += value;
+                value = -value;
+                cursor = tree[node].parent;
+            }
+        }
+
+        let root_visits: u32 = tree[0].children.iter().map(|&child| tree[child].visits).sum();
+        let distribution: VisitDistribution = tree[0].children.iter().map(|&child| {
+            let share = tree[child].visits as f32 / std::cmp::max(1, root_visits) as f32;
+            (tree[child].environment.state, share)
+        }).collect();
+
+        // Remember what the simulations found for each decision,
+        // weighted by the number of simulations behind it.
+        for &child in tree[0].children.iter() {
+            let node = &tree[child];
+            if node.visits == 0 {
+                continue;
+            }
+            let value_for_white = match environment.state.to_move {
+                Color::White => node.mean_value(),
+                Color::Black => -node.mean_value(),
+            };
+            self.experience.memorize_visits(&node.environment, value_for_white, node.visits as i32);
+        }
+
+        let decision = self.choose_by_visits(&tree);
+        (decision, distribution)
+    }
+
+    // PUCT: prefer decisions that have scored well, but keep
+    // trying those with a strong prior and few visits.
+    fn select_child(&self, tree: &[Node], index: usize) -> usize {
+        let parent = &tree[index];
+        let exploration = self.mcts.exploration_constant * (parent.visits as f32).sqrt();
+        let score = |child: usize| {
+            let node = &tree[child];
+            node.mean_value() + exploration * node.prior / (1 + node.visits) as f32
+        };
+
+        let mut best_child = parent.children[0];
+        let mut best_score = f32::NEG_INFINITY;
+        for &child in parent.children.iter() {
+            let child_score = score(child);
+            if child_score > best_score {
+                best_child = child;
+                best_score = child_score;
+            }
+        }
+        best_child
+    }
+
+    // Add a child for every available decision, with priors
+    // given by a softmax over the experienced values of the
+    // positions they lead to.
+    fn expand(&self, tree: &mut Vec<Node>, index: usize) {
+        let environment = tree[index].environment.clone();
+        let mover = environment.state.to_move;
+
+        let children: Vec<ChessEnvironment> = environment.available_decisions()
+            .into_iter()
+            .map(|decision| environment.branch(decision))
+            .collect();
+
+        let weights: Vec<f32> = children.iter().map(|child| {
+            let value_for_white = self.experience.value_of(&child.state);
+            match mover {
+                Color::White => value_for_white.exp(),
+                Color::Black => (-value_for_white).exp(),
+            }
+        }).collect();
+        let total_weight: f32 = weights.iter().sum();
+
+        for (child, weight) in children.into_iter().zip(weights) {
+            tree.push(Node::new(child, Some(index), weight / total_weight));
+            let child_index = tree.len() - 1;
+            tree[index].children.push(child_index);
+        }
+        tree[index].expanded = true;
+    }
+
+    // The value of a new leaf for the side to move, either
+    // directly or at the end of a short random rollout.
+    fn estimate(&mut self, environment: &ChessEnvironment) -> f32 {
+        self.positions_evaluated += 1;
+        let perspective = environment.state.to_move;
+
+        let mut rollout = environment.clone();
+        for _ in 0..self.mcts.rollout_depth {
+            match rollout.random_decision_using(&mut self.rng) {
+                Some(decision) if !rollout.is_terminated() => rollout.apply_change(decision),
+                _ => break,
+            }
+        }
+
+        match rollout.is_terminated() {
+            true => rollout.terminal_state(perspective).reward(),
+            false => self.leaf_value(&rollout.state, perspective),
+        }
+    }
+
+    // Play the most visited decision, or sample in proportion
+    // to visits raised to 1 / temperature.
+    fn choose_by_visits(&mut self, tree: &[Node]) -> GameState {
+        let children = &tree[0].children;
+        let most_visited = children.iter()
+            .max_by_key(|&&child| tree[child].visits)
+            .map(|&child| tree[child].environment.state)
+            .unwrap_or(tree[0].environment.state);
+
+        if self.mcts.temperature <= 0.0 {
+            return most_visited;
+        }
+
+        let weights: Vec<f32> = children.iter()
+            .map(|&child| (tree[child].visits as f32).powf(1.0 / self.mcts.temperature))
+            .collect();
+        let total_weight: f32 = weights.iter().sum();
+        if !total_weight.is_normal() {
+            return most_visited;
+        }
+
+        let mut remaining = self.rng.gen::<f32>() * total_weight;
+        for (&child, weight) in children.iter().zip(weights) {
+            remaining -= weight;
+            if remaining <= 0.0 {
+                return tree[child].environment.state;
+            }
+        }
+        most_visited
+    }
+}
+
+#[test]
+fn monte_carlo_search_test() {
+    let mut agent = ChessAgent::new();
+    agent.mcts.simulations = 300;
+
+    // Taking the undefended queen wins material
+    let environment = ChessEnvironment::from_fen("k7/8/8/3q4/8/8/8/K2R4 w - - 0 1").unwrap();
+    let (decision, distribution) = agent.monte_carlo_search(&environment);
+    assert!(decision.squares[35].is_some_and(|piece| piece.color == Color::White));
+
+    assert_eq!(distribution.len(), environment.available_decisions().len());
+    let total: f32 = distribution.iter().map(|(_, share)| share).sum();
+    assert!((total - 1.0).abs() < 0.001);
+}
@@ -0,0 +1,73 @@
+
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::ChessAgent;
+use crate::environment::*;
+
+mod alpha_beta;
+mod mcts;
+pub use mcts::*;
+
+mod limits;
+pub use limits::{Clock, SearchInfo, SearchLimits, MAX_SEARCH_DEPTH};
+pub(crate) use limits::SearchBudget;
+
+mod transposition;
+pub use transposition::*;
+
+mod quiescence;
+pub use quiescence::QuiescenceOptions;
+
+mod ordering;
+pub use ordering::MoveOrdering;
+
+// How an agent chooses between its available decisions
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Policy {
+    // Follow one sampled line of play per decision,
+    // exploring or exploiting experience at each ply.
+    SampledLine,
+
+    // Search every line to the agent's foresight with
+    // negamax, pruning lines that can't change the result.
+    AlphaBeta,
+
+    // Grow a search tree by repeated simulation, spending
+    // more visits on the decisions that look most promising.
+    MonteCarloTreeSearch,
+}
+
+impl Policy {
+    pub fn from_name(name: &str) -> Option<Policy> {
+        match name.to_lowercase().replace(&['_', '-', ' '][..], "").as_str() {
+            "sampledline" => Some(Policy::SampledLine),
+            "alphabeta" => Some(Policy::AlphaBeta),
+            "mcts" | "montecarlotreesearch" => Some(Policy::MonteCarloTreeSearch),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::SampledLine => "SampledLine",
+            Policy::AlphaBeta => "AlphaBeta",
+            Policy::MonteCarloTreeSearch => "MCTS",
+        }
+    }
+}
+
+impl ChessAgent {
+    // Experience is preferred when the agent has any, falling
+    // back to the material balance for unfamiliar positions.
+    fn leaf_value(&self, state: &GameState, perspective: Color) -> f32 {
+        let value_for_white = match self.experience.recollection_of(state) {
+            Some(recollection) => recollection.average_value,
+            None => return material_balance(state, perspective),
+        };
+
+        match perspective {
+            Color::White => value_for_white,
+            Color::Black => -value_for_white,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+
+use std::cmp::Reverse;
+use chess_engine::*;
+use crate::agent::ChessAgent;
+use crate::environment::*;
+use crate::notation::{describe_change, piece_index};
+use crate::zobrist::update_key;
+
+// Pawn, knight, bishop, rook, queen and king, by piece_index
+const PIECE_VALUES: [i32; 6] = [1, 3, 3, 5, 9, 100];
+
+const TABLE_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const KILLER_SCORES: [i32; 2] = [50_000, 49_000];
+// History scores stay below the killer moves
+const HISTORY_LIMIT: u32 = 40_000;
+
+// What the search has learned about which decisions tend to be
+// good, used to search the most promising ones first. The
+// sooner a good decision is searched, the more of the others
+// alpha-beta can rule out without searching them fully.
+#[derive(Clone, Debug)]
+pub struct MoveOrdering {
+    // For every ply, the last two quiet moves that
+    // ruled out the rest of their position's moves
+    killers: Vec<[Option<(usize, usize)>; 2]>,
+    // How much each move, from one square to another,
+    // has ruled out, weighted towards deep searches
+    history: Vec<u32>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering {
+            killers: vec![],
+            history: vec![0; 64 * 64],
+        }
+    }
+}
+
+impl MoveOrdering {
+    // Killer moves only hold for the position they were found
+    // in, while history fades so it follows the game along.
+    pub fn new_search(&mut self) {
+        self.killers.clear();
+        for score in self.history.iter_mut() {
+            *score /= 2;
+        }
+    }
+
+    // Remember a move that ended the search of a position early,
+    // where it was searched with the given depth remaining.
+    pub fn record_cutoff(&mut self, before: &GameState, after: &GameState, ply: i32, depth: i32) {
+        // Captures are searched early anyway
+        if is_capture(before, after) {
+            return;
+        }
+        let change = match describe_change(before, after) {
+            Some(change) => (change.from, change.to),
+            None => return,
+        };
+
+        let ply = ply.max(0) as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(change) {
+            killers[1] = killers[0];
+            killers[0] = Some(change);
+        }
+
+        let score = &mut self.history[change.0 * 64 + change.1];
+        *score = (*score + (depth * depth).max(1) as u32).min(HISTORY_LIMIT);
+    }
+
+    // Higher scores are searched first: the transposition table's
+    // best move, then captures of the most valuable victims by
+    // the least valuable attackers along with promotions, then
+    // killer moves, and finally quiet moves by their history.
+    pub fn score(&self, before: &GameState, after: &GameState, ply: i32, is_table_move: bool) -> i32 {
+        if is_table_move {
+            return TABLE_MOVE_SCORE;
+        }
+        let change = match describe_change(before, after) {
+            Some(change) => change,
+            None => return 0,
+        };
+        let value_of = |name: PieceName| PIECE_VALUES[piece_index(name)];
+
+        let mut score = 0;
+        if is_capture(before, after) {
+            // En passant leaves the square it takes on empty
+            let victim = before.squares[change.to].map_or(PIECE_VALUES[0], |piece| value_of(piece.name));
+            let attacker = before.squares[change.from].map_or(0, |piece| value_of(piece.name));
+            score += CAPTURE_SCORE + victim * 100 - attacker;
+        }
+        if let Some(promotion) = change.promotion {
+            score += CAPTURE_SCORE + value_of(promotion) * 100;
+        }
+        if score > 0 {
+            return score;
+        }
+
+        let killers = self.killers.get(ply.max(0) as usize).cloned().unwrap_or([None; 2]);
+        for (killer, killer_score) in killers.iter().zip(KILLER_SCORES.iter()) {
+            if *killer == Some((change.from, change.to)) {
+                return *killer_score;
+            }
+        }
+        self.history[change.from * 64 + change.to] as i32
+    }
+}
+
+impl ChessAgent {
+    // Sort decisions so the most promising are searched first. The
+    // table move is the key of the position the transposition
+    // table's best decision leads to.
+    pub fn order_decisions(
+        &self,
+        environment: &ChessEnvironment,
+        ply: i32,
+        decisions: &mut [GameState],
+        table_move: Option<u64>,
+    ) {
+        if !self.move_ordering {
+            return;
+        }
+
+        let state = environment.state;
+        let key = environment.position_key();
+        decisions.sort_by_cached_key(|decision| {
+            let is_table_move = table_move.is_some_and(|table_move| update_key(key, &state, decision) == table_move);
+            Reverse(self.ordering.score(&state, decision, ply, is_table_move))
+        });
+    }
+}
+
+#[test]
+fn move_ordering_test() {
+    use crate::notation::{coordinate_notation, resolve_coordinate_move};
+
+    let environment = ChessEnvironment::from_fen("k7/8/8/3q4/4P3/8/1p6/1R1Q3K w - - 0 1").unwrap();
+    let notation = |decision: &GameState| coordinate_notation(&environment.state, decision).unwrap();
+    let mut agent = ChessAgent::new();
+
+    // The pawn taking the queen comes before the queen or rook
+    // taking anything, and quiet moves come last.
+    let mut decisions = environment.available_decisions();
+    agent.order_decisions(&environment, 0, &mut decisions, None);
+    assert_eq!(notation(&decisions[0]), "e4d5");
+    assert_eq!(notation(&decisions[1]), "d1d5");
+    assert_eq!(notation(&decisions[2]), "b1b2");
+
+    // The table move comes before everything else
+    let quiet = resolve_coordinate_move(&environment.state, "d1h5").unwrap();
+    let table_move = environment.branch(quiet).position_key();
+    agent.order_decisions(&environment, 0, &mut decisions, Some(table_move));
+    assert_eq!(notation(&decisions[0]), "d1h5");
+
+    // Killer moves come right after captures
+    let killer = resolve_coordinate_move(&environment.state, "d1g4").unwrap();
+    agent.ordering.record_cutoff(&environment.state, &killer, 0, 3);
+    agent.order_decisions(&environment, 0, &mut decisions, None);
+    assert_eq!(notation(&decisions[3]), "d1g4");
+
+    // Searching the best decisions first rules out more of the rest
+    let environment = ChessEnvironment::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+    agent.policy = crate::agent::Policy::AlphaBeta;
+    agent.foresight = 3;
+    agent.transposition.buckets = 0;
+    agent.quiescence.enabled = false;
+    agent.react(&environment);
+    let ordered = agent.positions_evaluated;
+
+    agent.move_ordering = false;
+    agent.positions_evaluated = 0;
+    agent.react(&environment);
+    assert!(ordered < agent.positions_evaluated);
+}
@@ -0,0 +1,126 @@
+
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::ChessAgent;
+use crate::environment::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuiescenceOptions {
+    // Keep searching past the foresight until the position is
+    // quiet, rather than judging it in the middle of an exchange.
+    pub enabled: bool,
+
+    // Plies past the foresight before the search gives up
+    // on the position becoming quiet
+    pub max_depth: i32,
+
+    // Also follow checks on the first ply past the foresight,
+    // and every evasion when in check
+    pub checks: bool,
+
+    pub promotions: bool,
+}
+
+impl Default for QuiescenceOptions {
+    fn default() -> QuiescenceOptions {
+        QuiescenceOptions {
+            enabled: true,
+            max_depth: 6,
+            checks: false,
+            promotions: true,
+        }
+    }
+}
+
+impl ChessAgent {
+    // The value of a position for the side to move, following only
+    // captures (and, as configured, promotions and checks) until
+    // none are left. The side to move may also "stand pat" and
+    // decline them all, so the position's own value is a lower
+    // bound on its value.
+    pub fn quiescence(
+        &mut self,
+        environment: &ChessEnvironment,
+        depth: i32,
+        mut alpha: f32,
+        beta: f32,
+        extension: i32,
+    ) -> f32 {
+        self.positions_evaluated += 1;
+        if self.budget.spend() {
+            return 0.0;
+        }
+        let to_move = environment.state.to_move;
+        let discount = self.discount.powf(depth as f32);
+
+        if environment.is_terminated() {
+            return environment.terminal_state(to_move).reward() * discount;
+        }
+
+        let stand_pat = self.leaf_value(&environment.state, to_move) * discount;
+        if extension >= self.quiescence.max_depth {
+            return stand_pat;
+        }
+
+        // In check, declining to move isn't an option
+        let in_check = self.quiescence.checks && is_in_check(&environment.state);
+        let mut best_value = match in_check {
+            true => f32::NEG_INFINITY,
+            false => stand_pat,
+        };
+        if best_value >= beta {
+            return best_value;
+        }
+        alpha = alpha.max(best_value);
+
+        let mut decisions: Vec<GameState> = environment.available_decisions()
+            .into_iter()
+            .filter(|decision| in_check || self.is_noisy(&environment.state, decision, extension))
+            .collect();
+        self.order_decisions(environment, depth, &mut decisions, None);
+
+        for decision in decisions {
+            let next_environment = environment.branch(decision);
+            let value = -self.quiescence(&next_environment, depth + 1, -beta, -alpha, extension + 1);
+
+            best_value = best_value.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        best_value
+    }
+
+    // Decisions that could change the value of a position
+    // too much for it to be judged as it stands
+    fn is_noisy(&self, before: &GameState, after: &GameState, extension: i32) -> bool {
+        is_capture(before, after)
+            || (self.quiescence.promotions && is_promotion(before, after))
+            || (self.quiescence.checks && extension == 0 && is_in_check(after))
+    }
+}
+
+#[test]
+fn quiescence_test() {
+    let mut agent = ChessAgent::new();
+    agent.policy = crate::agent::Policy::AlphaBeta;
+    agent.foresight = 1;
+
+    // Taking the pawn on d5 looks like winning material, until
+    // it's seen that the queen is taken straight back.
+    let environment = ChessEnvironment::from_fen("k7/8/4p3/3p4/8/8/8/K2Q4 w - - 0 1").unwrap();
+    let queen_takes = |decision: GameState| decision.squares[35].is_some_and(|piece| piece.color == Color::White);
+
+    agent.quiescence.enabled = false;
+    assert!(queen_takes(agent.react(&environment)));
+
+    agent.quiescence.enabled = true;
+    assert!(!queen_takes(agent.react(&environment)));
+
+    // Standing pat is never worse than the position's own value
+    let value = agent.quiescence(&environment, 0, f32::NEG_INFINITY, f32::INFINITY, 0);
+    assert!(value >= material_balance(&environment.state, Color::White));
+}
@@ -0,0 +1,287 @@
+
+use serde::{Deserialize, Serialize};
+use crate::agent::ChessAgent;
+use crate::environment::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranspositionOptions {
+    // Each bucket holds two entries. At 0 the table is disabled.
+    pub buckets: usize,
+
+    // Keep entries from one move's search for the next, where
+    // they're replaced first. Otherwise every search starts
+    // with an empty table.
+    pub keep_between_moves: bool,
+}
+
+impl Default for TranspositionOptions {
+    fn default() -> TranspositionOptions {
+        TranspositionOptions {
+            buckets: 1 << 16,
+            keep_between_moves: true,
+        }
+    }
+}
+
+impl TranspositionOptions {
+    // The number of buckets that fit in the given memory
+    pub fn buckets_for_megabytes(megabytes: usize) -> usize {
+        megabytes * 1024 * 1024 / std::mem::size_of::<Bucket>()
+    }
+
+    pub fn megabytes(&self) -> usize {
+        self.buckets * std::mem::size_of::<Bucket>() / (1024 * 1024)
+    }
+}
+
+// How a stored value relates to the position's true value.
+// Searches that were cut off only establish a bound.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    // The value is at least this much
+    Lower,
+    // The value is at most this much
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TranspositionEntry {
+    pub key: u64,
+    // For the side to move
+    pub value: f32,
+    // The plies searched below the position
+    pub depth: i32,
+    pub bound: Bound,
+    // The key of the position the best decision leads to
+    pub best_move: Option<u64>,
+    // The search that stored the entry
+    generation: u8,
+}
+
+impl TranspositionEntry {
+    pub fn new(key: u64, value: f32, depth: i32, bound: Bound, best_move: Option<u64>) -> TranspositionEntry {
+        TranspositionEntry { key, value, depth, bound, best_move, generation: 0 }
+    }
+}
+
+// One entry kept for as long as nothing deeper comes along,
+// and another that always takes the newest entry, so that
+// shallow results are still kept without losing deep ones.
+#[derive(Copy, Clone, Debug, Default)]
+struct Bucket {
+    depth_preferred: Option<TranspositionEntry>,
+    always_replace: Option<TranspositionEntry>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TableStatistics {
+    pub probes: u64,
+    // Probes that found an entry for the position
+    pub hits: u64,
+    // Hits that ended the search of the position
+    pub cutoffs: u64,
+    pub stores: u64,
+    // Stores that displaced another position's entry
+    pub replacements: u64,
+}
+
+impl TableStatistics {
+    pub fn hit_rate(&self) -> f64 {
+        match self.probes {
+            0 => 0.0,
+            probes => self.hits as f64 / probes as f64,
+        }
+    }
+}
+
+// Search results by zobrist key, so a position reached by
+// more than one line is only searched once.
+#[derive(Clone, Debug, Default)]
+pub struct TranspositionTable {
+    // Allocated on first use, since most agents never search
+    buckets: Vec<Bucket>,
+    capacity: usize,
+    generation: u8,
+    statistics: TableStatistics,
+}
+
+impl TranspositionTable {
+    pub fn new(buckets: usize) -> TranspositionTable {
+        TranspositionTable { capacity: buckets, ..TranspositionTable::default() }
+    }
+
+    // Get ready for a new search with the given options.
+    // Statistics only ever cover the latest search.
+    pub fn prepare(&mut self, options: &TranspositionOptions) {
+        if options.buckets != self.capacity || !options.keep_between_moves {
+            *self = TranspositionTable::new(options.buckets);
+        } else {
+            self.generation = self.generation.wrapping_add(1);
+            self.statistics = TableStatistics::default();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = TranspositionTable::new(self.capacity);
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<TranspositionEntry> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+
+        self.statistics.probes += 1;
+        let bucket = &self.buckets[self.index(key)];
+        let entry = [bucket.depth_preferred, bucket.always_replace]
+            .iter()
+            .flatten()
+            .find(|entry| entry.key == key)
+            .cloned();
+        if entry.is_some() {
+            self.statistics.hits += 1;
+        }
+        entry
+    }
+
+    pub fn store(&mut self, mut entry: TranspositionEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.buckets.is_empty() {
+            self.buckets = vec![Bucket::default(); self.capacity];
+        }
+
+        entry.generation = self.generation;
+        let generation = self.generation;
+        let index = self.index(entry.key);
+        let bucket = &mut self.buckets[index];
+
+        // Entries left from earlier searches give way to anything new
+        let replace_preferred = match bucket.depth_preferred {
+            None => true,
+            Some(existing) => {
+                existing.key == entry.key || existing.generation != generation || entry.depth >= existing.depth
+            },
+        };
+
+        let displaced = match replace_preferred {
+            true => {
+                // An entry pushed out of the depth-preferred slot is
+                // still newer than whatever's in the other one.
+                let displaced = match bucket.depth_preferred.replace(entry) {
+                    Some(existing) if existing.key != entry.key => bucket.always_replace.replace(existing),
+                    _ => None,
+                };
+                if bucket.always_replace.is_some_and(|other| other.key == entry.key) {
+                    bucket.always_replace = None;
+                }
+                displaced
+            },
+            false => bucket.always_replace.replace(entry),
+        };
+
+        self.statistics.stores += 1;
+        if displaced.is_some_and(|existing| existing.key != entry.key) {
+            self.statistics.replacements += 1;
+        }
+    }
+
+    pub fn statistics(&self) -> TableStatistics {
+        self.statistics
+    }
+
+    pub(crate) fn record_cutoff(&mut self) {
+        self.statistics.cutoffs += 1;
+    }
+
+    // The share of entries in use, from 0 to 1
+    pub fn occupancy(&self) -> f64 {
+        if self.buckets.is_empty() {
+            return 0.0;
+        }
+        let used: usize = self.buckets.iter()
+            .map(|bucket| bucket.depth_preferred.is_some() as usize + bucket.always_replace.is_some() as usize)
+            .sum();
+        used as f64 / (self.buckets.len() * 2) as f64
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.buckets.len() as u64) as usize
+    }
+}
+
+impl ChessAgent {
+    // Values are discounted by how far they lie from the root, so
+    // they're stored as seen from the position itself, and
+    // discounted again for whatever ply they're found at.
+    pub(crate) fn probe_table(&mut self, environment: &ChessEnvironment, ply: i32) -> Option<TranspositionEntry> {
+        let discount = self.discount.powf(ply as f32);
+        let entry = self.transposition_table.probe(environment.position_key())?;
+        Some(TranspositionEntry { value: entry.value * discount, ..entry })
+    }
+
+    // Store the result of searching a position to the agent's foresight
+    pub(crate) fn store_in_table(
+        &mut self,
+        environment: &ChessEnvironment,
+        ply: i32,
+        value: f32,
+        bound: Bound,
+        best_move: Option<u64>,
+    ) {
+        // Abandoned searches leave nothing worth keeping
+        let discount = self.discount.powf(ply as f32);
+        if self.budget.is_exhausted() || discount == 0.0 {
+            return;
+        }
+        let key = environment.position_key();
+        let depth = self.foresight - ply;
+        self.transposition_table.store(TranspositionEntry::new(key, value / discount, depth, bound, best_move));
+    }
+}
+
+#[test]
+fn transposition_table_test() {
+    let mut table = TranspositionTable::new(1);
+    assert_eq!(table.probe(1), None);
+
+    table.store(TranspositionEntry::new(1, 0.5, 4, Bound::Exact, Some(7)));
+    assert_eq!(table.probe(1).map(|entry| entry.value), Some(0.5));
+
+    // A shallower entry doesn't displace a deeper one,
+    // but is still kept in the other slot.
+    table.store(TranspositionEntry::new(2, 0.1, 2, Bound::Lower, None));
+    table.store(TranspositionEntry::new(3, 0.2, 1, Bound::Upper, None));
+    assert_eq!(table.probe(1).map(|entry| entry.depth), Some(4));
+    assert_eq!(table.probe(2), None);
+    assert_eq!(table.probe(3).map(|entry| entry.bound), Some(Bound::Upper));
+
+    // Deeper entries take the preferred slot, pushing
+    // the old one into the other slot.
+    table.store(TranspositionEntry::new(4, 0.3, 6, Bound::Exact, None));
+    assert_eq!(table.probe(4).map(|entry| entry.depth), Some(6));
+    assert_eq!(table.probe(1).map(|entry| entry.depth), Some(4));
+    assert_eq!(table.occupancy(), 1.0);
+
+    let statistics = table.statistics();
+    assert_eq!((statistics.probes, statistics.hits, statistics.stores), (6, 5, 4));
+
+    // Without keeping entries between moves, each search starts afresh
+    table.prepare(&TranspositionOptions { buckets: 1, keep_between_moves: false });
+    assert_eq!(table.probe(4), None);
+    assert_eq!(table.statistics().stores, 0);
+
+    // Transpositions are found, without changing the decision
+    let environment = ChessEnvironment::from_fen("k7/8/8/3q4/8/8/8/K2R4 w - - 0 1").unwrap();
+    let mut agent = ChessAgent::new();
+    agent.policy = crate::agent::Policy::AlphaBeta;
+    agent.foresight = 4;
+    let decision = agent.react(&environment);
+    assert!(agent.transposition_table.statistics().cutoffs > 0);
+
+    agent.transposition.buckets = 0;
+    assert_eq!(agent.react(&environment).to_string(), decision.to_string());
+    assert_eq!(agent.transposition_table.statistics(), TableStatistics::default());
+}
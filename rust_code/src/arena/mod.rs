@@ -0,0 +1,247 @@
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::{AgentConfig, ChessAgent};
+use crate::environment::*;
+use crate::profile::{load_profile, ProfileError};
+use crate::random::SeededRng;
+use crate::training::Opponent;
+
+// One side of an arena match: an agent, as described by its
+// settings, or one of the fixed opponents used in training.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Contestant {
+    Agent { name: String, config: AgentConfig },
+    Opponent(Opponent),
+}
+
+impl Contestant {
+    // "Random" and "Material" name the fixed opponents,
+    // and any other name is a saved profile.
+    pub fn from_name(name: &str, profile_directory: &str) -> Result<Contestant, ProfileError> {
+        match Opponent::from_name(name) {
+            Some(opponent @ Opponent::Random) | Some(opponent @ Opponent::Material) => {
+                Ok(Contestant::Opponent(opponent))
+            },
+            _ => Ok(Contestant::Agent {
+                name: name.to_string(),
+                config: load_profile(profile_directory, name)?,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Contestant::Agent { name, .. } => name,
+            Contestant::Opponent(opponent) => opponent.name(),
+        }
+    }
+
+    // A fresh player for a single game, so that nothing an
+    // agent memorizes during one game carries into the next.
+    // Agents read their experience, but never write it back,
+    // and are seeded from the given generator. They explore as
+    // they would once training is over, however far it got.
+    pub fn player<R: Rng>(&self, rng: &mut R) -> std::io::Result<Player> {
+        match self {
+            Contestant::Agent { config, .. } => {
+                let mut agent = ChessAgent::from_config(config);
+                agent.exploration = agent.exploration.settled();
+                agent.rng = SeededRng::new(rng.gen());
+                agent.experience.long_term_recall()?;
+                Ok(Player::Agent(Box::new(agent)))
+            },
+            Contestant::Opponent(opponent) => Ok(Player::Opponent(*opponent)),
+        }
+    }
+}
+
+pub enum Player {
+    Agent(Box<ChessAgent>),
+    Opponent(Opponent),
+}
+
+impl Player {
+    pub fn choose<R: Rng>(&mut self, environment: &ChessEnvironment, rng: &mut R) -> Option<GameState> {
+        if environment.is_terminated() {
+            return None;
+        }
+
+        match self {
+            Player::Agent(agent) => Some(agent.react_as_mover(environment)),
+            Player::Opponent(opponent) => opponent.choose(environment, rng),
+        }
+    }
+}
+
+// Play a game from the given position until it ends, or until
+// the turn limit is reached, where a turn is a move by each side.
+pub fn play_game<R: Rng>(
+    white: &mut Player,
+    black: &mut Player,
+    mut environment: ChessEnvironment,
+    turn_limit: i32,
+    rng: &mut R,
+) -> ChessEnvironment {
+    for _ in 0..turn_limit * 2 {
+        let player = match environment.state.to_move {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+
+        match player.choose(&environment, rng) {
+            Some(decision) => environment.apply_change(decision),
+            None => break,
+        }
+    }
+    environment
+}
+
+// The results of a match, from the first contestant's perspective
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchResult {
+    // Games cut short by the turn limit count as draws
+    pub fn record(&mut self, environment: &ChessEnvironment, perspective: Color) {
+        match environment.is_terminated() {
+            false => self.draws += 1,
+            true => match environment.terminal_state(perspective) {
+                TerminalState::Win => self.wins += 1,
+                TerminalState::Loss => self.losses += 1,
+                TerminalState::Draw(_) => self.draws += 1,
+            },
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // The share of points won, counting draws as half a point
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.0) / games as f64,
+        }
+    }
+
+    // The rating difference that would make this score the
+    // expected one. Infinite when every game was won or lost.
+    pub fn elo_difference(&self) -> f64 {
+        elo_from_score(self.score())
+    }
+
+    // Half the width of the 95% confidence interval around
+    // the Elo difference, from the variance of the game scores.
+    pub fn elo_error_margin(&self) -> f64 {
+        let games = self.games() as f64;
+        let score = self.score();
+        if games == 0.0 || score <= 0.0 || score >= 1.0 {
+            return f64::INFINITY;
+        }
+
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let deviation = (variance / games).sqrt();
+
+        let low = elo_from_score(score - 1.96 * deviation);
+        let high = elo_from_score(score + 1.96 * deviation);
+        (high - low) / 2.0
+    }
+
+    // The probability that the first contestant is the stronger,
+    // judged by decisive games alone.
+    pub fn likelihood_of_superiority(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        let difference = self.wins as f64 - self.losses as f64;
+        0.5 * (1.0 + erf(difference / (2.0 * decisive).sqrt()))
+    }
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    match score {
+        score if score <= 0.0 => f64::NEG_INFINITY,
+        score if score >= 1.0 => f64::INFINITY,
+        score => 400.0 * (score / (1.0 - score)).log10(),
+    }
+}
+
+// Abramowitz and Stegun's approximation, 7.1.26,
+// accurate to within 1.5e-7.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t * (0.254_829_592
+        + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    match x < 0.0 {
+        true => -value,
+        false => value,
+    }
+}
+
+#[test]
+fn match_result_test() {
+    let result = MatchResult { wins: 6, draws: 3, losses: 1 };
+    assert_eq!(result.games(), 10);
+    assert_eq!(result.score(), 0.75);
+    assert!((result.elo_difference() - 190.85).abs() < 0.01);
+    assert!((result.likelihood_of_superiority() - 0.9716).abs() < 0.001);
+    assert!(result.elo_error_margin() > 100.0 && result.elo_error_margin().is_finite());
+
+    let even = MatchResult { wins: 2, draws: 4, losses: 2 };
+    assert_eq!(even.elo_difference(), 0.0);
+    assert!((even.likelihood_of_superiority() - 0.5).abs() < 1e-6);
+
+    let sweep = MatchResult { wins: 4, draws: 0, losses: 0 };
+    assert_eq!(sweep.elo_difference(), f64::INFINITY);
+    assert_eq!(sweep.elo_error_margin(), f64::INFINITY);
+
+    // A mated side has no choice to make
+    let (mut environment, mate) = rook_mate_in_one();
+    environment.apply_change(mate);
+    let (mut white, mut black) = (Player::Opponent(Opponent::Random), Player::Opponent(Opponent::Random));
+    let played = play_game(&mut white, &mut black, environment, 10, &mut crate::random::SeededRng::new(0));
+    assert_eq!(played.plies_played(), 1);
+
+    let mut result = MatchResult::default();
+    result.record(&played, Color::White);
+    assert_eq!(result, MatchResult { wins: 1, draws: 0, losses: 0 });
+}
+
+#[test]
+fn seeded_game_test() {
+    let path = std::env::temp_dir().join(format!("seeded_game_test_{}.bin", std::process::id()));
+    let agent = Contestant::Agent {
+        name: "Seeded".to_string(),
+        config: AgentConfig {
+            foresight: 1,
+            experience_file: path.to_str().unwrap().to_string(),
+            ..AgentConfig::default()
+        },
+    };
+    let opponent = Contestant::Opponent(Opponent::Random);
+
+    // A seed plays the same game every time, ply for ply
+    let play = |seed: u64| {
+        let mut rng = SeededRng::new(seed);
+        let mut white = agent.player(&mut rng).unwrap();
+        let mut black = opponent.player(&mut rng).unwrap();
+        let played = play_game(&mut white, &mut black, ChessEnvironment::new(), 10, &mut rng);
+        played.history().into_iter().map(|ply| ply.action).collect::<Vec<_>>()
+    };
+    assert_eq!(play(3), play(3));
+    assert_ne!(play(3), play(4));
+
+    std::fs::remove_file(&path).unwrap();
+}
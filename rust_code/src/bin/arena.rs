@@ -0,0 +1,218 @@
+
+use chess_engine::*;
+use reinforcement_learning_chess::*;
+
+const USAGE: &str = "Usage: arena --players <name,name,...> [flags]
+
+Plays every pair of players against each other, alternating colors,
+and reports how each pair fared. Players are saved profiles, or
+Random or Material for the fixed training opponents.
+
+  --players <names>         two or more players, separated by commas
+  --games <n>               games per pair of players
+  --turns <n>               moves by each side per game before it's abandoned
+  --openings <file>         start games from these FEN positions, one per line
+  --profile-dir <directory> where profiles are kept
+  --seed <n>                seed for every random choice, so matches can be repeated
+  --pgn <file>              append every game to this PGN file
+  --help                    show this message";
+
+const FLAGS: [&str; 7] = ["players", "games", "turns", "openings", "profile-dir", "seed", "pgn"];
+
+pub struct ArenaOptions {
+    pub games: u32,
+    pub turn_limit: i32,
+    pub openings: Vec<String>,
+    pub seed: Option<u64>,
+    pub pgn_file: Option<String>,
+}
+
+// Play every game between two contestants, giving each the
+// white pieces in turn. Each opening is played twice in a
+// row, once with each contestant as white.
+fn play_match(
+    first: &Contestant,
+    second: &Contestant,
+    options: &ArenaOptions,
+    games_played: &mut u64,
+) -> Result<MatchResult, String> {
+    let mut result = MatchResult::default();
+
+    for game in 0..options.games {
+        let first_color = match game % 2 {
+            0 => Color::White,
+            _ => Color::Black,
+        };
+        let environment = match options.openings.is_empty() {
+            true => ChessEnvironment::new(),
+            false => {
+                let fen = &options.openings[(game as usize / 2) % options.openings.len()];
+                ChessEnvironment::from_fen(fen).map_err(|error| format!("{}: {}", fen, error))?
+            },
+        };
+
+        let mut rng = match options.seed {
+            Some(seed) => SeededRng::stream(seed, *games_played),
+            None => SeededRng::unseeded(),
+        };
+
+        let mut player = |contestant: &Contestant| {
+            contestant.player(&mut rng).map_err(|error| format!("Unable to load {}: {}", contestant.name(), error))
+        };
+        let (mut white, mut black, white_name, black_name) = match first_color {
+            Color::White => (player(first)?, player(second)?, first.name(), second.name()),
+            Color::Black => (player(second)?, player(first)?, second.name(), first.name()),
+        };
+        let environment = play_game(&mut white, &mut black, environment, options.turn_limit, &mut rng);
+        result.record(&environment, first_color);
+        *games_played += 1;
+
+        if let Some(filename) = &options.pgn_file {
+            let mut game_record = PgnGame::from_environment(&environment);
+            game_record.set_tag("Event", "Arena");
+            game_record.set_tag("Round", &games_played.to_string());
+            game_record.set_tag("White", white_name);
+            game_record.set_tag("Black", black_name);
+            if let Err(error) = game_record.append_to_file(filename) {
+                println!("Unable to write {}: {}", filename, error);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn report(first: &str, second: &str, result: &MatchResult) {
+    println!(
+        "{} vs {}: {} games, +{} ={} -{}, score {:.1}%",
+        first,
+        second,
+        result.games(),
+        result.wins,
+        result.draws,
+        result.losses,
+        result.score() * 100.0,
+    );
+    println!(
+        "  Elo difference {:.1} +/- {:.1}, likelihood of superiority {:.1}%",
+        result.elo_difference(),
+        result.elo_error_margin(),
+        result.likelihood_of_superiority() * 100.0,
+    );
+}
+
+// Openings are FEN records, one per line. Blank lines
+// and lines starting with # are skipped.
+fn read_openings(filename: &str) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(filename).map_err(|error| format!("{}: {}", filename, error))?;
+
+    let mut openings = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        ChessEnvironment::from_fen(line).map_err(|error| format!("{}: {}: {}", filename, line, error))?;
+        openings.push(line.to_string());
+    }
+    Ok(openings)
+}
+
+fn arena_from_flags(flags: &Flags) -> Result<(Vec<Contestant>, ArenaOptions), String> {
+    let profile_directory = flags.value("profile-dir").unwrap_or(PROFILE_DIRECTORY);
+    let names: Vec<&str> = flags.value("players")
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.len() < 2 {
+        return Err("at least two players are needed".to_string());
+    }
+
+    let mut contestants = vec![];
+    for name in names {
+        let contestant = Contestant::from_name(name, profile_directory)
+            .map_err(|error| format!("{}: {}", name, error))?;
+        contestants.push(contestant);
+    }
+
+    let options = ArenaOptions {
+        games: flags.parsed("games").map_err(|error| error.to_string())?.unwrap_or(10),
+        turn_limit: flags.parsed("turns").map_err(|error| error.to_string())?.unwrap_or(100),
+        openings: match flags.value("openings") {
+            Some(filename) => read_openings(filename)?,
+            None => vec![],
+        },
+        seed: flags.parsed("seed").map_err(|error| error.to_string())?,
+        pgn_file: flags.value("pgn").map(str::to_string),
+    };
+    if options.turn_limit < 1 {
+        return Err(format!("turns must be at least 1, found {}", options.turn_limit));
+    }
+    Ok((contestants, options))
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flags = match Flags::parse(&args, &FLAGS, &["help"]) {
+        Ok(flags) => flags,
+        Err(error) => {
+            println!("{}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    if flags.switch("help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let (contestants, options) = match arena_from_flags(&flags) {
+        Ok(arena) => arena,
+        Err(error) => {
+            println!("{}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    // Every contestant's total, over all of its matches
+    let mut totals = vec![MatchResult::default(); contestants.len()];
+    let mut games_played = 0;
+
+    for first in 0..contestants.len() {
+        for second in first + 1..contestants.len() {
+            let result = match play_match(&contestants[first], &contestants[second], &options, &mut games_played) {
+                Ok(result) => result,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(1);
+                },
+            };
+            report(contestants[first].name(), contestants[second].name(), &result);
+
+            totals[first].wins += result.wins;
+            totals[first].draws += result.draws;
+            totals[first].losses += result.losses;
+            totals[second].wins += result.losses;
+            totals[second].draws += result.draws;
+            totals[second].losses += result.wins;
+        }
+    }
+
+    if contestants.len() > 2 {
+        let mut standings: Vec<(&Contestant, &MatchResult)> = contestants.iter().zip(totals.iter()).collect();
+        standings.sort_by(|(_, a), (_, b)| b.score().partial_cmp(&a.score()).unwrap());
+
+        println!("\nStandings:");
+        for (contestant, total) in standings {
+            println!(
+                "  {:<20} {:>5.1}% +{} ={} -{}",
+                contestant.name(),
+                total.score() * 100.0,
+                total.wins,
+                total.draws,
+                total.losses,
+            );
+        }
+    }
+}
@@ -6,6 +6,9 @@ use std::collections::HashMap;
 
 pub struct GameOptions {
     pub agent_playing_as: Color,
+    pub pgn_file: Option<String>,
+    pub starting_fen: Option<String>,
+    pub seed: Option<u64>,
 }
 
 
@@ -14,16 +17,28 @@ pub fn play_vs_human(options: GameOptions) {
     // Create an agent, and attempt to restore
     // experiences created by previous training.
     let mut agent = ChessAgent::new();
+    if let Some(seed) = options.seed {
+        agent.rng = SeededRng::new(seed);
+    }
+    if let Err(error) = agent.experience.long_term_recall() {
+        println!("Unable to open {}: {}", agent.experience.long_term_memory_file, error);
+    }
 
     // Create a new game environment
-    let mut environment = ChessEnvironment::new();
+    let mut environment = match &options.starting_fen {
+        None => ChessEnvironment::new(),
+        Some(fen) => match ChessEnvironment::from_fen(fen) {
+            Ok(environment) => environment,
+            Err(error) => {
+                println!("Unable to start from {}: {}", fen, error);
+                return;
+            },
+        },
+    };
 
     // Set the agent to play as the correct color
     agent.playing_as = options.agent_playing_as;
 
-    let mut move_count = 0;
-    let mut move_count_display = String::new();
-
     println!("\nNew Game:\n");
     println!("{}\n", environment.state.to_string()); 
 
@@ -31,16 +46,13 @@ pub fn play_vs_human(options: GameOptions) {
     // Play until the game is finished
     while false ==  environment.is_terminated() {
 
-        // Increment move_count
-        match environment.state.to_move {
-            Color::White => {
-                move_count += 1;
-                move_count_display = format!("{}.", move_count);
-            },
-            Color::Black => {
-                move_count_display = format!("{}...", move_count);
-            }
-        }
+        // Derive the move count from the game history,
+        // since takebacks can move it in either direction.
+        let move_count = environment.fullmove_number();
+        let move_count_display = match environment.state.to_move {
+            Color::White => format!("{}.", move_count),
+            Color::Black => format!("{}...", move_count),
+        };
 
         // Agent's turn to move
         if environment.state.to_move == agent.playing_as {
@@ -65,13 +77,42 @@ pub fn play_vs_human(options: GameOptions) {
             let mut input = String::new();
             while !legal_inputs.contains_key(&input) {
                 println!("Legal moves: {:?}", legal_inputs.keys());
-                input = get_input("Choose your move: ");
+                input = get_input("Choose your move (or undo/redo): ");
                 println!();
+
+                if input == "undo" || input == "redo" {
+                    break;
+                }
             }
 
             println!("\r");
             println!("\r");
 
+            // Take back the human's last move along with the agent's
+            // reply, or replay a pair of moves that were taken back.
+            if input == "undo" {
+                if environment.plies_played() < 2 {
+                    println!("There are no moves to take back.\n");
+                } else {
+                    environment.undo();
+                    environment.undo();
+                    println!("Took back your last move:\n");
+                    println!("{}\n", environment.state);
+                }
+                continue;
+            }
+
+            if input == "redo" {
+                if environment.redo() {
+                    environment.redo();
+                    println!("Replayed moves:\n");
+                    println!("{}\n", environment.state);
+                } else {
+                    println!("There are no moves to replay.\n");
+                }
+                continue;
+            }
+
             // Apply the chosen move
             let chosen_next_state = legal_inputs.get(&input).unwrap();
             environment.apply_change(*chosen_next_state);
@@ -88,13 +129,64 @@ pub fn play_vs_human(options: GameOptions) {
     match environment.terminal_state(agent.playing_as) {
         TerminalState::Loss => println!("You win!"),
         TerminalState::Win => println!("You lose!"),
-        TerminalState::Draw => println!("Draw!"),
+        TerminalState::Draw(reason) => println!("Draw! ({:?})", reason),
     }
+
+    // Record the game for later review
+    if let Some(filename) = &options.pgn_file {
+        let mut game = PgnGame::from_environment(&environment);
+        game.set_tag("Event", "Play vs Human");
+        let (white, black) = match agent.playing_as {
+            Color::White => ("ChessAgent", "Human"),
+            Color::Black => ("Human", "ChessAgent"),
+        };
+        game.set_tag("White", white);
+        game.set_tag("Black", black);
+        game.record_agent_settings(&agent);
+
+        match game.append_to_file(filename) {
+            Ok(_) => println!("Game saved to {}", filename),
+            Err(error) => println!("Unable to write {}: {}", filename, error),
+        }
+    }
+}
+
+fn exit_with_usage(error: CliError) -> ! {
+    println!("{}\n\nUsage: play_vs_human [--seed <n>] [--pgn <path>]", error);
+    std::process::exit(2);
 }
 
 pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flags = Flags::parse(&args, &["seed", "pgn"], &[]).unwrap_or_else(|error| exit_with_usage(error));
+    let seed = flags.parsed("seed").unwrap_or_else(|error| exit_with_usage(error));
+
+    // Games are saved to games.pgn unless another file is
+    // named, and an empty name turns saving them off.
+    let pgn_file = match flags.value("pgn") {
+        Some("") => None,
+        Some(path) => Some(path.to_string()),
+        None => Some("./games.pgn".to_string()),
+    };
+
+    // Keep asking until the position can be set up
+    let starting_fen = loop {
+        let fen = get_input("Starting FEN (blank for the standard position): ");
+        if fen.is_empty() {
+            break None;
+        }
+
+        match ChessEnvironment::from_fen(&fen) {
+            Ok(_) => break Some(fen),
+            Err(error) => println!("Invalid FEN: {}", error),
+        }
+    };
+
     play_vs_human(GameOptions {
         agent_playing_as: Color::White,
+        pgn_file,
+        starting_fen,
+        seed,
     });
 }
 
@@ -1,76 +1,528 @@
 
 use chess_engine::*;
+use rand::Rng;
 use reinforcement_learning_chess::*;
-
-pub struct TrainingOptions {
-    pub game_limit: i32,
-    pub turn_limit: i32,
-}
-
+use std::path::Path;
+use std::str::FromStr;
 
 // TODO debug setting
 // TODO reverse gameboard string
-// TODO agent time limit / turn
-// TODO use correct fen format, but slice it to use as a hash
-// TODO use experience pruning
-// TODO JIT long term memory
 
-pub fn training_pipeline(options: TrainingOptions) {
+pub fn training_pipeline(config: TrainingConfig) {
+    let options = config.options;
+
     // Create an agent, and attempt to restore
     // experiences created by previous training.
-    let mut agent = ChessAgent::new();
-    agent.experience.long_term_recall();
+    let mut agent = ChessAgent::from_config(&config.agent);
+    if let Some(seed) = options.seed {
+        agent.rng = SeededRng::new(seed);
+    }
+    if let Err(error) = agent.experience.long_term_recall() {
+        println!("Unable to open {}: {}", agent.experience.long_term_memory_file, error);
+    }
+
+    // Seed experience from a database of existing games
+    if let Some(filename) = &options.study_pgn_file {
+        study_pgn_file(&mut agent, filename);
+    }
+
+    train(agent, options, TrainingStatistics::default());
+}
+
+// Continue a training run from the game after a checkpoint
+pub fn resume_training(directory: &str) {
+    let checkpoint = match Checkpoint::load(directory) {
+        Ok(checkpoint) => checkpoint,
+        Err(error) => {
+            println!("Unable to resume from {}: {}", directory, error);
+            return;
+        },
+    };
+
+    // Refuse before the agent's store is replaced
+    if !checkpoint.options.resumable() {
+        println!(
+            "Unable to resume from {}: only runs against the random or material opponent, \
+             or in self-play without a rival, can be resumed",
+            directory,
+        );
+        return;
+    }
+
+    let agent = match checkpoint.restore_agent(directory) {
+        Ok(agent) => agent,
+        Err(error) => {
+            println!("Unable to restore the agent from {}: {}", directory, error);
+            return;
+        },
+    };
+
+    println!(
+        "Resuming after game {} of {}",
+        checkpoint.statistics.games_played,
+        checkpoint.options.game_limit,
+    );
+    train(agent, checkpoint.options, checkpoint.statistics);
+}
+
+fn train(mut agent: ChessAgent, options: TrainingOptions, mut statistics: TrainingStatistics) {
+    // Train from a particular position, like an endgame,
+    // or from the standard starting position.
+    let mut environment = match &options.starting_fen {
+        None => ChessEnvironment::new(),
+        Some(fen) => match ChessEnvironment::from_fen(fen) {
+            Ok(environment) => environment,
+            Err(error) => {
+                println!("Unable to start from {}: {}", fen, error);
+                return;
+            },
+        },
+    };
+
+    // In self-play against a second agent, it shares the first
+    // agent's settings but learns from its own experience.
+    let mut opponent_agent = match (options.opponent, &options.opponent_experience_file) {
+        (Opponent::SelfPlay, Some(path)) => {
+            let mut config = agent.config();
+            config.experience_file = path.clone();
+            let mut opponent_agent = ChessAgent::from_config(&config);
+            opponent_agent.rng = SeededRng::new(agent.rng.gen());
+            if let Err(error) = opponent_agent.experience.long_term_recall() {
+                println!("Unable to open {}: {}", path, error);
+            }
+            Some(opponent_agent)
+        },
+        _ => None,
+    };
+
+    // Against the league, an opponent is drawn from its snapshots
+    // for every game, starting from a snapshot of the agent itself.
+    let mut league = match options.opponent {
+        Opponent::League => match League::open(&options.league) {
+            Ok(league) => Some(league),
+            Err(error) => {
+                println!("Unable to open the league in {}: {}", options.league.directory, error);
+                return;
+            },
+        },
+        _ => None,
+    };
+    if let Some(league) = league.as_mut().filter(|league| league.is_empty()) {
+        if let Err(error) = league.add_snapshot(&mut agent, &options, &statistics) {
+            println!("Unable to add a snapshot: {}", error);
+            return;
+        }
+    }
 
     // Play until the game limit is reached
-    for game_count in 0..options.game_limit {
+    for game_count in statistics.games_played..options.game_limit {
+
+        // The opponent's choices can be seeded so that runs can
+        // be repeated. Each game has its own stream, so a resumed
+        // run makes the same choices as one that wasn't stopped.
+        let mut rng = match options.seed {
+            Some(seed) => SeededRng::stream(seed, game_count as u64),
+            None => SeededRng::unseeded(),
+        };
 
+        let snapshot = league.as_ref().map(|league| league.sample(&mut rng).unwrap_or(0));
+        let mut snapshot_agent = match (&mut league, snapshot) {
+            (Some(league), Some(index)) => {
+                let name = league.snapshots()[index].name.clone();
+                match league.load_opponent(index) {
+                    Ok(snapshot_agent) => {
+                        snapshot_agent.rng = SeededRng::new(rng.gen());
+                        Some(snapshot_agent)
+                    },
+                    Err(error) => {
+                        println!("Unable to load snapshot {}: {}", name, error);
+                        return;
+                    },
+                }
+            },
+            _ => None,
+        };
 
-        // Create a new environment, and switch sides
-        let mut environment = ChessEnvironment::new();
-        agent.playing_as = match agent.playing_as {
-            Color::White => Color::Black,
-            Color::Black=> Color::White,
+        // Reset the environment, and switch sides
+        environment.reset();
+        agent.playing_as = match game_count % 2 {
+            0 => Color::Black,
+            _ => Color::White,
         };
+        agent.games_played = game_count as u32;
+        if let (Opponent::SelfPlay, Some(opponent_agent)) = (options.opponent, &mut opponent_agent) {
+            opponent_agent.games_played = game_count as u32;
+        }
+        let positions_evaluated = agent.positions_evaluated;
+
+        // Play until the turn limit is reached, where
+        // a turn is a move by each side
+        for _ in 0..options.turn_limit * 2 {
+            let action = match environment.state.to_move == agent.playing_as {
+                // Apply the agent's choice
+                true => agent.react(&environment),
+
+                // Apply a choice on behalf of the opponent, which in
+                // self-play is the agent itself or its rival, and in
+                // the league is a snapshot.
+                false => match (options.opponent, &mut opponent_agent, &mut snapshot_agent) {
+                    (Opponent::SelfPlay, Some(opponent_agent), _) => opponent_agent.react_as_mover(&environment),
+                    (Opponent::SelfPlay, None, _) => agent.react_as_mover(&environment),
+                    (Opponent::League, _, Some(snapshot_agent)) => snapshot_agent.react_as_mover(&environment),
+                    (opponent, _, _) => match opponent.choose(&environment, &mut rng) {
+                        Some(decision) => decision,
+                        None => break,
+                    },
+                },
+            };
 
-        // Play until the turn limit is reached
-        for _ in 0..options.turn_limit {
-            if environment.is_terminated() {
+            let (_, _, done, _) = environment.step(action);
+            if done {
                 break;
             }
-            
-            // Apply the agent's choice
-            let chosen_next_state = agent.react(&environment);
-            environment.apply_change(chosen_next_state);
+        }
+
+        // Every move of a self-play game was an agent's own, so
+        // the result says something about all of them.
+        if options.opponent == Opponent::SelfPlay {
+            agent.study_environment(&environment);
+            if let Some(opponent_agent) = &mut opponent_agent {
+                opponent_agent.study_environment(&environment);
+            }
+        }
+
+        statistics.record_game(&environment, agent.playing_as);
+        if let (Some(league), Some(index)) = (&mut league, snapshot) {
+            if let Err(error) = league.record_result(index, &environment, agent.playing_as) {
+                println!("Unable to record the result against {}: {}", league.snapshots()[index].name, error);
+            }
+        }
+        statistics.positions_evaluated += agent.positions_evaluated - positions_evaluated;
 
-            // Apply a random choice on behalf of
-            // an imaginary opponent.
-            environment.apply_change_randomly();
+        // Record the game for later review
+        if let Some(filename) = &options.pgn_file {
+            let mut game = PgnGame::from_environment(&environment);
+            game.set_tag("Event", "Training");
+            game.set_tag("Round", &(game_count + 1).to_string());
+            let opponent_name = match (options.opponent, &league, snapshot) {
+                (_, Some(league), Some(index)) => format!("ChessAgent {}", league.snapshots()[index].name),
+                (Opponent::SelfPlay, _, _) if opponent_agent.is_none() => "ChessAgent".to_string(),
+                (opponent, _, _) => opponent.name().to_string(),
+            };
+            let (white, black) = match agent.playing_as {
+                Color::White => ("ChessAgent", opponent_name.as_str()),
+                Color::Black => (opponent_name.as_str(), "ChessAgent"),
+            };
+            game.set_tag("White", white);
+            game.set_tag("Black", black);
+            game.record_agent_settings(&agent);
+
+            if let Err(error) = game.append_to_file(filename) {
+                println!("Unable to write {}: {}", filename, error);
+            }
         }
 
-        if agent.experience.len() >= agent.memory_purge_threshold {
-            agent.experience.purge_weak_memories();
+        let memorized = match agent.experience.len() >= agent.memory_purge_threshold {
+            true => agent.experience.purge_weak_memories(),
+            false => agent.experience.long_term_memorize(),
+        };
+        if let Err(error) = memorized {
+            println!("Unable to write {}: {}", agent.experience.long_term_memory_file, error);
+        }
+        // Snapshots are frozen, so only a self-play rival's
+        // experience is kept.
+        if let (Opponent::SelfPlay, Some(opponent_agent)) = (options.opponent, &mut opponent_agent) {
+            if let Err(error) = opponent_agent.experience.long_term_memorize() {
+                println!("Unable to write {}: {}", opponent_agent.experience.long_term_memory_file, error);
+            }
+        }
+
+        if let Some(league) = &mut league {
+            if statistics.games_played % std::cmp::max(1, options.league.snapshot_interval) == 0 {
+                match league.add_snapshot(&mut agent, &options, &statistics) {
+                    Ok(path) => println!("Added snapshot {}", path.display()),
+                    Err(error) => println!("Unable to add a snapshot: {}", error),
+                }
+            }
+        }
+
+        // Save progress periodically, and at the end of the run
+        if let Some(directory) = &options.checkpoint_directory {
+            let games_played = statistics.games_played;
+            if games_played % std::cmp::max(1, options.checkpoint_interval) == 0 || games_played == options.game_limit {
+                let checkpoint = Checkpoint {
+                    agent: agent.config(),
+                    options: options.clone(),
+                    statistics: statistics.clone(),
+                    agent_rng: agent.rng.clone(),
+                };
+                match checkpoint.save(&mut agent, directory) {
+                    Ok(path) => println!("Saved checkpoint {}", path.display()),
+                    Err(error) => println!("Unable to save checkpoint: {}", error),
+                }
+            }
         }
-    
-        agent.experience.long_term_memorize();
     }
+
+    println!(
+        "Played {} games: {} won, {} lost, {} drawn, {} unfinished",
+        statistics.games_played,
+        statistics.wins,
+        statistics.losses,
+        statistics.draws,
+        statistics.unfinished,
+    );
 }
 
+fn study_pgn_file(agent: &mut ChessAgent, filename: &str) {
+    let games = match read_pgn_file(filename) {
+        Ok(games) => games,
+        Err(error) => {
+            println!("Unable to study {}: {}", filename, error);
+            return;
+        },
+    };
+
+    let mut games_studied = 0;
+    for (index, game) in games.iter().enumerate() {
+        match game.replay() {
+            Ok(replayed) => {
+                // Games without a result have nothing to teach
+                if let Some(value) = replayed.result.value_for_white() {
+                    agent.study_game(&replayed.states, value);
+                    games_studied += 1;
+                }
+            },
+            Err(error) => println!("Skipping game {} of {}: {}", index + 1, filename, error),
+        }
+    }
+
+    println!("Studied {} of {} games from {}", games_studied, games.len(), filename);
+}
+
+const USAGE: &str = "Usage: training [flags]
+
+With no flags, training options are prompted for.
+
+  --config <file>               read options and agent settings from a RON or JSON file
+  --profile <name>              use the agent settings saved under this name
+  --save-profile <name>         save the agent settings under this name
+  --save-config <file>          save the whole configuration to a RON or JSON file
+  --games <n>                   number of games to play
+  --turns <n>                   moves by each side per game before it's abandoned
+  --foresight <n>               plies the agent looks ahead
+  --discount <f>                discount applied to each ply of distance
+  --exploration <f>             the agent's propensity to explore, from 0 to 1
+  --exploration-strategy <name> Propensity, EpsilonLinear, EpsilonExponential,
+                                Boltzmann or UCB
+  --policy <name>               SampledLine, AlphaBeta or MCTS
+  --experience <path>           the agent's experience store
+  --opponent <name>             Random, Material, SelfPlay or League
+  --opponent-experience <path>  in self-play, a second agent's experience store
+  --league-dir <directory>      where the league's snapshots are kept
+  --snapshot-interval <n>       games between snapshots added to the league
+  --max-snapshots <n>           snapshots kept in the league, or 0 for all
+  --league-weighting <name>     Uniform, Recent or Prioritized
+  --seed <n>                    seed for every random choice, so runs can be repeated
+  --fen <fen>                   start every game from this position
+  --pgn <file>                  append every game to this PGN file
+  --study <file>                study the games in this PGN file first
+  --checkpoint-dir <directory>  save checkpoints in this directory
+  --checkpoint-interval <n>     games between checkpoints
+  --resume <checkpoint>         continue the run saved in a checkpoint, unless it
+                                played a self-play rival or the league
+  --help                        show this message";
+
+const FLAGS: [&str; 25] = [
+    "config", "profile", "save-profile", "save-config", "games", "turns", "foresight", "discount",
+    "exploration", "exploration-strategy", "policy", "experience", "opponent", "opponent-experience",
+    "league-dir", "snapshot-interval", "max-snapshots", "league-weighting", "seed", "fen", "pgn",
+    "study", "checkpoint-dir", "checkpoint-interval", "resume",
+];
+
 pub fn main() {
-    let options = prompt_training_options();
-    training_pipeline(options);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flags = match Flags::parse(&args, &FLAGS, &["help"]) {
+        Ok(flags) => flags,
+        Err(error) => {
+            println!("{}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    if flags.switch("help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Some(directory) = flags.value("resume") {
+        resume_training(directory);
+        return;
+    }
+
+    let config = match flags.is_empty() {
+        true => Ok(prompt_training_config()),
+        false => training_config_from_flags(&flags),
+    };
+
+    let config = match config.and_then(|config| config.validate().map(|_| config)) {
+        Ok(config) => config,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(2);
+        },
+    };
+
+    // Settings are saved once they're known to be valid,
+    // so that they can be used to repeat this run.
+    if let Some(name) = flags.value("save-profile") {
+        match save_profile(&config.agent, PROFILE_DIRECTORY, name, ProfileFormat::Ron) {
+            Ok(path) => println!("Saved profile {}", path.display()),
+            Err(error) => println!("Unable to save profile {}: {}", name, error),
+        }
+    }
+    if let Some(filename) = flags.value("save-config") {
+        match save_settings(&config, Path::new(filename)) {
+            Ok(()) => println!("Saved config {}", filename),
+            Err(error) => println!("Unable to save config {}: {}", filename, error),
+        }
+    }
+
+    training_pipeline(config);
+}
+
+// Start from the config file, if there is one, and a saved
+// profile's agent settings, and let any other flags override them.
+fn training_config_from_flags(flags: &Flags) -> Result<TrainingConfig, CliError> {
+    let mut config = match flags.value("config") {
+        Some(filename) => TrainingConfig::load(filename)?,
+        None => TrainingConfig::default(),
+    };
+    if let Some(name) = flags.value("profile") {
+        config.agent = load_profile(PROFILE_DIRECTORY, name).map_err(config_error)?;
+    }
+    let options = &mut config.options;
+    let agent = &mut config.agent;
+
+    if let Some(games) = flags.parsed("games")? {
+        options.game_limit = games;
+    }
+    if let Some(turns) = flags.parsed("turns")? {
+        options.turn_limit = turns;
+    }
+    if let Some(foresight) = flags.parsed("foresight")? {
+        agent.foresight = foresight;
+    }
+    if let Some(discount) = flags.parsed("discount")? {
+        agent.discount = discount;
+    }
+    if let Some(exploration) = flags.parsed("exploration")? {
+        agent.exploration_propensity = exploration;
+    }
+    if let Some(name) = flags.value("exploration-strategy") {
+        agent.exploration = Exploration::from_name(name).ok_or_else(|| invalid_value("exploration-strategy", name))?;
+    }
+    if let Some(name) = flags.value("policy") {
+        agent.policy = Policy::from_name(name).ok_or_else(|| invalid_value("policy", name))?;
+    }
+    if let Some(path) = flags.value("experience") {
+        agent.experience_file = path.to_string();
+    }
+    if let Some(name) = flags.value("opponent") {
+        options.opponent = Opponent::from_name(name).ok_or_else(|| invalid_value("opponent", name))?;
+    }
+    if let Some(path) = flags.value("opponent-experience") {
+        options.opponent_experience_file = Some(path.to_string());
+    }
+    if let Some(directory) = flags.value("league-dir") {
+        options.league.directory = directory.to_string();
+    }
+    if let Some(interval) = flags.parsed("snapshot-interval")? {
+        options.league.snapshot_interval = interval;
+    }
+    if let Some(max_snapshots) = flags.parsed("max-snapshots")? {
+        options.league.max_snapshots = max_snapshots;
+    }
+    if let Some(name) = flags.value("league-weighting") {
+        options.league.weighting = SnapshotWeighting::from_name(name)
+            .ok_or_else(|| invalid_value("league-weighting", name))?;
+    }
+    if let Some(seed) = flags.parsed("seed")? {
+        options.seed = Some(seed);
+    }
+    if let Some(fen) = flags.value("fen") {
+        options.starting_fen = Some(fen.to_string());
+    }
+    if let Some(filename) = flags.value("pgn") {
+        options.pgn_file = Some(filename.to_string());
+    }
+    if let Some(filename) = flags.value("study") {
+        options.study_pgn_file = Some(filename.to_string());
+    }
+    if let Some(directory) = flags.value("checkpoint-dir") {
+        options.checkpoint_directory = Some(directory.to_string());
+    }
+    if let Some(interval) = flags.parsed("checkpoint-interval")? {
+        options.checkpoint_interval = interval;
+    }
+
+    Ok(config)
 }
 
-fn prompt_training_options() -> TrainingOptions {
-    let game_limit: i32 = get_input("game_limit: ") 
-        .parse()
-        .unwrap();
+fn invalid_value(flag: &str, value: &str) -> CliError {
+    CliError::InvalidValue { flag: flag.to_string(), value: value.to_string() }
+}
 
-    let turn_limit: i32 = get_input("turn_limit: ") 
-        .parse()
-        .unwrap();
+// Prompt until the answer can be read, using the
+// default when the answer is left blank.
+fn prompt_parsed<T: FromStr>(prompt: &str, default: Option<T>) -> T {
+    let mut default = default;
+    loop {
+        let input = get_input(prompt);
+        if input.is_empty() {
+            if let Some(value) = default.take() {
+                return value;
+            }
+        }
 
-    TrainingOptions {
-        game_limit,
-        turn_limit,
+        match input.trim().parse() {
+            Ok(value) => return value,
+            Err(_) => println!("Unable to read {:?}, please try again", input),
+        }
     }
 }
+
+fn prompt_optional(prompt: &str) -> Option<String> {
+    match get_input(prompt).as_str() {
+        "" => None,
+        input => Some(input.to_string()),
+    }
+}
+
+fn prompt_training_config() -> TrainingConfig {
+    let mut config = TrainingConfig::default();
+    let options = &mut config.options;
+
+    options.game_limit = prompt_parsed("game_limit: ", None);
+    options.turn_limit = prompt_parsed("turn_limit: ", None);
+    options.pgn_file = prompt_optional("pgn_file (blank to skip): ");
+    options.study_pgn_file = prompt_optional("study_pgn_file (blank to skip): ");
+    options.starting_fen = prompt_optional("starting_fen (blank for the standard position): ");
+
+    config.agent.policy = loop {
+        match get_input("policy (SampledLine, AlphaBeta or MCTS, blank for SampledLine): ").as_str() {
+            "" => break Policy::SampledLine,
+            name => match Policy::from_name(name) {
+                Some(policy) => break policy,
+                None => println!("Unknown policy {}", name),
+            },
+        }
+    };
+
+    options.checkpoint_directory = prompt_optional("checkpoint_directory (blank to skip): ");
+    if options.checkpoint_directory.is_some() {
+        options.checkpoint_interval = prompt_parsed("checkpoint_interval (games, blank for 100): ", Some(100));
+    }
+
+    config
+}
@@ -0,0 +1,328 @@
+
+use chess_engine::*;
+use reinforcement_learning_chess::*;
+
+use std::io::{stdin, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// The limits that can accompany a "go" command. Times
+// are given in milliseconds.
+#[derive(Default)]
+pub struct GoOptions {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoOptions {
+    pub fn parse(args: &[&str]) -> GoOptions {
+        let mut options = GoOptions::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut number = || args.next().and_then(|value| value.parse::<u64>().ok());
+            match *arg {
+                "depth" => options.depth = number().map(|depth| depth as i32),
+                "nodes" => options.nodes = number(),
+                "movetime" => options.movetime = number(),
+                "wtime" => options.wtime = number(),
+                "btime" => options.btime = number(),
+                "winc" => options.winc = number(),
+                "binc" => options.binc = number(),
+                "movestogo" => options.movestogo = number(),
+                "infinite" => options.infinite = true,
+                _ => (),
+            }
+        }
+
+        options
+    }
+
+    // The limits on a search for the given side. An infinite
+    // search only ends when the GUI sends "stop".
+    pub fn limits(&self, to_move: Color, stop: Arc<AtomicBool>) -> SearchLimits {
+        let mut limits = SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            stop: Some(stop),
+            infinite: self.infinite,
+            ..SearchLimits::default()
+        };
+        if self.infinite {
+            return limits;
+        }
+
+        let (time, increment) = match to_move {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+        limits.movetime = self.movetime.map(Duration::from_millis);
+        limits.clock = time.map(|time| Clock {
+            remaining: Duration::from_millis(time),
+            increment: Duration::from_millis(increment.unwrap_or(0)),
+            moves_to_go: self.movestogo.map(|moves| moves as u32),
+        });
+        limits
+    }
+}
+
+pub struct UciEngine {
+    agent: Option<ChessAgent>,
+    environment: ChessEnvironment,
+    search: Option<JoinHandle<ChessAgent>>,
+    // Raised to end the running search early
+    stop: Arc<AtomicBool>,
+}
+
+impl UciEngine {
+    pub fn new() -> UciEngine {
+        UciEngine {
+            agent: Some(ChessAgent::new()),
+            environment: ChessEnvironment::new(),
+            search: None,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Respond to a single line from the GUI, returning
+    // false once the engine has been asked to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+
+        match command {
+            "uci" => {
+                let agent = self.agent();
+                println!("id name ReinforcementLearningChess");
+                println!("id author ml_chess");
+                println!("option name Foresight type spin default {} min 1 max 64", agent.foresight);
+                println!("option name Discount type string default {}", agent.discount);
+                println!("option name ExplorationPropensity type string default {}", agent.exploration_propensity);
+                println!("option name ExperiencePath type string default ./experience");
+                println!("option name Policy type combo default {} var SampledLine var AlphaBeta var MCTS", agent.policy.name());
+                println!("option name Simulations type spin default {} min 1 max 1000000", agent.mcts.simulations);
+                println!("option name ExplorationConstant type string default {}", agent.mcts.exploration_constant);
+                println!("option name Temperature type string default {}", agent.mcts.temperature);
+                println!("option name Hash type spin default {} min 0 max 4096", agent.transposition.megabytes());
+                println!("option name KeepHash type check default {}", agent.transposition.keep_between_moves);
+                println!("option name Quiescence type check default {}", agent.quiescence.enabled);
+                println!("option name Clear Hash type button");
+                println!("uciok");
+            },
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.wait_for_search();
+                self.environment = ChessEnvironment::new();
+            },
+            "setoption" => self.set_option(args),
+            "position" => self.set_position(args),
+            "go" => self.go(GoOptions::parse(args)),
+            "stop" => self.stop_search(),
+            "quit" => {
+                self.stop_search();
+                return false;
+            },
+            _ => (),
+        }
+
+        true
+    }
+
+    // The agent is away while a search is running,
+    // so wait for it to come back before using it.
+    fn agent(&mut self) -> &mut ChessAgent {
+        self.wait_for_search();
+        self.agent.as_mut().expect("Agent was lost during a search")
+    }
+
+    // Cut the running search short, which still
+    // reports the best move it has found.
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait_for_search();
+    }
+
+    fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            match search.join() {
+                Ok(agent) => self.agent = Some(agent),
+                Err(_) => {
+                    println!("info string search failed, restarting agent");
+                    self.agent = Some(ChessAgent::new());
+                },
+            }
+        }
+    }
+
+    // setoption name <name> [value <value>]
+    fn set_option(&mut self, args: &[&str]) {
+        let value_position = args.iter().position(|arg| *arg == "value");
+        let name = args[..value_position.unwrap_or(args.len())]
+            .iter()
+            .filter(|arg| **arg != "name")
+            .cloned()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value = match value_position {
+            Some(index) => args[index + 1..].join(" "),
+            None => String::new(),
+        };
+
+        let agent = self.agent();
+        let applied = match name.to_lowercase().as_str() {
+            "foresight" => value.parse().map(|foresight| agent.foresight = foresight).is_ok(),
+            "discount" => value.parse().map(|discount| agent.discount = discount).is_ok(),
+            "explorationpropensity" | "exploration_propensity" => {
+                value.parse().map(|propensity| agent.exploration_propensity = propensity).is_ok()
+            },
+            "policy" => Policy::from_name(&value).map(|policy| agent.policy = policy).is_some(),
+            "simulations" => value.parse().map(|simulations| agent.mcts.simulations = simulations).is_ok(),
+            "explorationconstant" => value.parse().map(|constant| agent.mcts.exploration_constant = constant).is_ok(),
+            "temperature" => value.parse().map(|temperature| agent.mcts.temperature = temperature).is_ok(),
+            "hash" => value.parse().map(|megabytes| {
+                agent.transposition.buckets = TranspositionOptions::buckets_for_megabytes(megabytes)
+            }).is_ok(),
+            "keephash" => value.parse().map(|keep| agent.transposition.keep_between_moves = keep).is_ok(),
+            "quiescence" => value.parse().map(|enabled| agent.quiescence.enabled = enabled).is_ok(),
+            "clear hash" => {
+                agent.transposition_table.clear();
+                true
+            },
+            "experiencepath" | "experience" => agent.use_experience_from(&value).is_ok(),
+            _ => false,
+        };
+
+        if !applied {
+            println!("info string unable to set option {} to {}", name, value);
+        }
+    }
+
+    // position [startpos | fen <fen>] [moves <move> ...]
+    fn set_position(&mut self, args: &[&str]) {
+        self.wait_for_search();
+        let moves_position = args.iter().position(|arg| *arg == "moves");
+        let setup = &args[..moves_position.unwrap_or(args.len())];
+
+        let environment = match setup.split_first() {
+            Some((&"startpos", _)) => Ok(ChessEnvironment::new()),
+            Some((&"fen", fen)) => ChessEnvironment::from_fen(&fen.join(" ")),
+            _ => {
+                println!("info string expected startpos or fen");
+                return;
+            },
+        };
+
+        self.environment = match environment {
+            Ok(environment) => environment,
+            Err(error) => {
+                println!("info string invalid fen: {}", error);
+                return;
+            },
+        };
+
+        if let Some(index) = moves_position {
+            for notation in &args[index + 1..] {
+                match resolve_coordinate_move(&self.environment.state, notation) {
+                    Some(state) => self.environment.apply_change(state),
+                    None => {
+                        println!("info string illegal move {}", notation);
+                        return;
+                    },
+                }
+            }
+        }
+    }
+
+    // Search in the background, so the GUI can keep talking to
+    // the engine. The search deepens until it runs into one of
+    // the limits, or is stopped.
+    fn go(&mut self, options: GoOptions) {
+        self.wait_for_search();
+        let mut agent = match self.agent.take() {
+            Some(agent) => agent,
+            None => return,
+        };
+        let environment = self.environment.clone();
+        self.stop = Arc::new(AtomicBool::new(false));
+        let limits = options.limits(environment.state.to_move, self.stop.clone());
+
+        self.search = Some(thread::spawn(move || {
+            if environment.available_decisions().is_empty() {
+                println!("bestmove 0000");
+                return agent;
+            }
+
+            agent.playing_as = environment.state.to_move;
+            let decision = agent.react_within(&environment, &limits);
+
+            println!(
+                "info depth {} nodes {} time {} hashfull {}",
+                agent.last_search.depth,
+                agent.last_search.nodes,
+                agent.last_search.elapsed.as_millis(),
+                (agent.transposition_table.occupancy() * 1000.0) as u32,
+            );
+            let statistics = agent.transposition_table.statistics();
+            println!(
+                "info string hash hits {:.1}% of {} probes, {} cutoffs",
+                statistics.hit_rate() * 100.0,
+                statistics.probes,
+                statistics.cutoffs,
+            );
+
+            match coordinate_notation(&environment.state, &decision) {
+                Some(notation) => println!("bestmove {}", notation),
+                None => println!("bestmove 0000"),
+            }
+            agent
+        }));
+    }
+}
+
+impl Default for UciEngine {
+    fn default() -> UciEngine {
+        UciEngine::new()
+    }
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let seed = match seed_flag(&args) {
+        Ok(seed) => seed,
+        Err(error) => {
+            println!("{}\n\nUsage: uci [--seed <n>]", error);
+            std::process::exit(2);
+        },
+    };
+
+    let mut engine = UciEngine::new();
+    if let (Some(seed), Some(agent)) = (seed, engine.agent.as_mut()) {
+        agent.rng = SeededRng::new(seed);
+    }
+
+    let stdin = stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if !engine.handle(&line) {
+            break;
+        }
+    }
+
+    engine.wait_for_search();
+}
@@ -0,0 +1,243 @@
+
+use chess_engine::*;
+use reinforcement_learning_chess::*;
+
+use std::io::{stdin, BufRead};
+use std::time::Duration;
+
+// Time controls as set by the level, st and time commands.
+// Clock readings are in centiseconds, as xboard sends them.
+#[derive(Default)]
+pub struct TimeControl {
+    pub moves_per_session: u32,
+    pub base_seconds: f32,
+    pub increment_seconds: f32,
+    pub seconds_per_move: Option<f32>,
+    pub engine_clock: Option<u64>,
+    pub opponent_clock: Option<u64>,
+}
+
+impl TimeControl {
+    // The limits on the engine's next search, once it has
+    // made the given number of moves this game.
+    pub fn limits(&self, moves_made: u32, depth_limit: Option<i32>) -> SearchLimits {
+        let moves_to_go = match self.moves_per_session {
+            0 => None,
+            moves => Some(moves - moves_made % moves),
+        };
+
+        // Until the GUI reports the clock, the whole
+        // session's time is assumed to be left.
+        let remaining = match (self.engine_clock, self.base_seconds > 0.0) {
+            (Some(centiseconds), _) => Some(Duration::from_millis(centiseconds * 10)),
+            (None, true) => Some(Duration::from_secs_f32(self.base_seconds)),
+            (None, false) => None,
+        };
+        SearchLimits {
+            depth: depth_limit,
+            movetime: self.seconds_per_move.map(Duration::from_secs_f32),
+            clock: remaining.map(|remaining| Clock {
+                remaining,
+                increment: Duration::from_secs_f32(self.increment_seconds),
+                moves_to_go,
+            }),
+            ..SearchLimits::default()
+        }
+    }
+}
+
+// A number of seconds, which can't be turned into a
+// duration unless it's finite and not negative
+fn parse_seconds(text: &str) -> Option<f32> {
+    text.parse::<f32>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+}
+
+pub struct XboardEngine {
+    agent: ChessAgent,
+    environment: ChessEnvironment,
+    // The color the engine plays, or None in force mode
+    engine_color: Option<Color>,
+    depth_limit: Option<i32>,
+    time_control: TimeControl,
+}
+
+impl XboardEngine {
+    pub fn new() -> XboardEngine {
+        XboardEngine {
+            agent: ChessAgent::new(),
+            environment: ChessEnvironment::new(),
+            engine_color: Some(Color::Black),
+            depth_limit: None,
+            time_control: TimeControl::default(),
+        }
+    }
+
+    // Respond to a single command, returning false
+    // once the engine has been asked to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "post" | "nopost" | "hard" | "easy" | "computer" => (),
+            "protover" => {
+                println!(
+                    "feature myname=\"ReinforcementLearningChess\" ping=1 setboard=1 usermove=1 \
+                    playother=1 san=0 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 done=1"
+                );
+            },
+            "ping" => println!("pong {}", args.join(" ")),
+            "new" => {
+                self.environment = ChessEnvironment::new();
+                self.engine_color = Some(Color::Black);
+                self.depth_limit = None;
+                self.time_control = TimeControl::default();
+            },
+            "force" | "result" => self.engine_color = None,
+            "go" => {
+                self.engine_color = Some(self.environment.state.to_move);
+                self.move_if_engine_to_play();
+            },
+            "playother" => {
+                self.engine_color = Some(opposite(self.environment.state.to_move));
+            },
+            "setboard" => match ChessEnvironment::from_fen(&args.join(" ")) {
+                Ok(environment) => self.environment = environment,
+                Err(error) => println!("tellusererror Illegal position: {}", error),
+            },
+            "usermove" => self.user_move(args.first().cloned().unwrap_or("")),
+            "undo" => {
+                self.environment.undo();
+            },
+            "remove" => {
+                self.environment.undo();
+                self.environment.undo();
+            },
+            "sd" => self.depth_limit = args.first().and_then(|depth| depth.parse().ok()),
+            "st" => {
+                self.time_control.seconds_per_move = args.first().and_then(|seconds| parse_seconds(seconds));
+            },
+            "level" => self.set_level(args),
+            "time" => self.time_control.engine_clock = args.first().and_then(|time| time.parse().ok()),
+            "otim" => self.time_control.opponent_clock = args.first().and_then(|time| time.parse().ok()),
+            "quit" => return false,
+
+            // Moves may also be sent without the usermove prefix
+            _ => match resolve_coordinate_move(&self.environment.state, command) {
+                Some(_) => self.user_move(command),
+                None => println!("Error (unknown command): {}", command),
+            },
+        }
+
+        true
+    }
+
+    // level MPS BASE INC, where BASE is minutes or minutes:seconds
+    fn set_level(&mut self, args: &[&str]) {
+        if args.len() < 3 {
+            println!("Error (too few parameters): level");
+            return;
+        }
+
+        let base_seconds = match args[1].split_once(':') {
+            Some((minutes, seconds)) => {
+                parse_seconds(minutes).unwrap_or(0.0) * 60.0 + parse_seconds(seconds).unwrap_or(0.0)
+            },
+            None => parse_seconds(args[1]).unwrap_or(0.0) * 60.0,
+        };
+
+        self.time_control.moves_per_session = args[0].parse().unwrap_or(0);
+        self.time_control.base_seconds = base_seconds;
+        self.time_control.increment_seconds = parse_seconds(args[2]).unwrap_or(0.0);
+        self.time_control.seconds_per_move = None;
+    }
+
+    fn user_move(&mut self, notation: &str) {
+        match resolve_coordinate_move(&self.environment.state, notation) {
+            Some(state) => {
+                self.environment.apply_change(state);
+                self.announce_result();
+                self.move_if_engine_to_play();
+            },
+            None => println!("Illegal move: {}", notation),
+        }
+    }
+
+    // Search within the time control and depth limit, if any
+    fn move_if_engine_to_play(&mut self) {
+        if self.engine_color != Some(self.environment.state.to_move) || self.environment.is_terminated() {
+            return;
+        }
+
+        let moves_made = self.environment.plies_played() as u32 / 2;
+        let limits = self.time_control.limits(moves_made, self.depth_limit);
+
+        self.agent.playing_as = self.environment.state.to_move;
+        let decision = self.agent.react_within(&self.environment, &limits);
+
+        match coordinate_notation(&self.environment.state, &decision) {
+            Some(notation) => {
+                self.environment.apply_change(decision);
+                println!("move {}", notation);
+                self.announce_result();
+            },
+            None => println!("resign"),
+        }
+    }
+
+    fn announce_result(&self) {
+        if !self.environment.is_terminated() {
+            return;
+        }
+
+        match self.environment.terminal_state(Color::White) {
+            TerminalState::Win => println!("1-0 {{White mates}}"),
+            TerminalState::Loss => println!("0-1 {{Black mates}}"),
+            TerminalState::Draw(reason) => println!("1/2-1/2 {{{:?}}}", reason),
+        }
+    }
+}
+
+impl Default for XboardEngine {
+    fn default() -> XboardEngine {
+        XboardEngine::new()
+    }
+}
+
+fn opposite(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let seed = match seed_flag(&args) {
+        Ok(seed) => seed,
+        Err(error) => {
+            println!("{}\n\nUsage: xboard [--seed <n>]", error);
+            std::process::exit(2);
+        },
+    };
+
+    let mut engine = XboardEngine::new();
+    if let Some(seed) = seed {
+        engine.agent.rng = SeededRng::new(seed);
+    }
+
+    let stdin = stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if !engine.handle(&line) {
+            break;
+        }
+    }
+}
@@ -0,0 +1,130 @@
+
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownFlag(String),
+    UnexpectedArgument(String),
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+    UnreadableConfig(String),
+    InvalidConfig(String),
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CliError::UnknownFlag(flag) => write!(f, "unknown flag --{}", flag),
+            CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument {}, flags start with --", arg),
+            CliError::MissingValue(flag) => write!(f, "--{} needs a value", flag),
+            CliError::InvalidValue { flag, value } => write!(f, "invalid value for --{}: {}", flag, value),
+            CliError::UnreadableConfig(message) => write!(f, "unable to read config: {}", message),
+            CliError::InvalidConfig(message) => write!(f, "invalid config: {}", message),
+        }
+    }
+}
+
+// Flags given as "--name value" or "--name=value". Switches
+// are flags that never take a value, like "--help".
+pub struct Flags {
+    values: Vec<(String, String)>,
+    switches: Vec<String>,
+}
+
+impl Flags {
+    // Parse command line arguments, not including the program
+    // name. Every flag must be one of those named.
+    pub fn parse(args: &[String], flags: &[&str], switches: &[&str]) -> Result<Flags, CliError> {
+        let mut parsed = Flags {
+            values: vec![],
+            switches: vec![],
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => return Err(CliError::UnexpectedArgument(arg.to_string())),
+            };
+
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+
+            if switches.contains(&name) {
+                if inline_value.is_some() {
+                    return Err(CliError::InvalidValue { flag: name.to_string(), value: flag.to_string() });
+                }
+                parsed.switches.push(name.to_string());
+            } else if flags.contains(&name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args.next().cloned().ok_or_else(|| CliError::MissingValue(name.to_string()))?,
+                };
+                parsed.values.push((name.to_string(), value));
+            } else {
+                return Err(CliError::UnknownFlag(name.to_string()));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.switches.is_empty()
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    // The last value given for a flag
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.iter()
+            .rev()
+            .find(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.value(name) {
+            Some(value) => value.parse().map(Some).map_err(|_| CliError::InvalidValue {
+                flag: name.to_string(),
+                value: value.to_string(),
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+// For binaries whose only flag is --seed
+pub fn seed_flag(args: &[String]) -> Result<Option<u64>, CliError> {
+    Flags::parse(args, &["seed"], &[])?.parsed("seed")
+}
+
+#[test]
+fn flags_test() {
+    let args: Vec<String> = ["--games", "10", "--discount=0.8", "--help", "--games", "12"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+    let flags = Flags::parse(&args, &["games", "discount", "pgn"], &["help"]).unwrap();
+    assert!(flags.switch("help"));
+    assert_eq!(flags.parsed::<i32>("games"), Ok(Some(12)));
+    assert_eq!(flags.parsed::<f32>("discount"), Ok(Some(0.8)));
+    assert_eq!(flags.value("pgn"), None);
+    assert_eq!(
+        flags.parsed::<i32>("discount"),
+        Err(CliError::InvalidValue { flag: "discount".to_string(), value: "0.8".to_string() }),
+    );
+
+    let parse = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Flags::parse(&args, &["games"], &["help"]).err()
+    };
+    assert_eq!(parse(&["--turns", "3"]), Some(CliError::UnknownFlag("turns".to_string())));
+    assert_eq!(parse(&["--games"]), Some(CliError::MissingValue("games".to_string())));
+    assert_eq!(parse(&["games"]), Some(CliError::UnexpectedArgument("games".to_string())));
+}
@@ -1,6 +1,9 @@
 
 use std::io::{stdin,stdout,Write};
 
+mod flags;
+pub use flags::*;
+
 
 pub fn get_input(prompt: &str) -> String {
     // Write a prompt to the terminal
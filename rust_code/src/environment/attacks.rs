@@ -0,0 +1,113 @@
+
+use chess_engine::*;
+use crate::notation::piece_index;
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+// Whether any piece of the given color attacks a square
+pub fn is_attacked(state: &GameState, square: usize, by: Color) -> bool {
+    let (rank, file) = ((square / 8) as i32, (square % 8) as i32);
+    let attacker_at = |rank: i32, file: i32, names: &[PieceName]| {
+        if !(0..8).contains(&rank) || !(0..8).contains(&file) {
+            return false;
+        }
+        match state.squares[(rank * 8 + file) as usize] {
+            Some(piece) => {
+                piece.color == by && names.iter().any(|name| piece_index(*name) == piece_index(piece.name))
+            },
+            None => false,
+        }
+    };
+
+    // Pawns attack diagonally towards the other side
+    let pawn_rank = match by {
+        Color::White => rank - 1,
+        Color::Black => rank + 1,
+    };
+    if attacker_at(pawn_rank, file - 1, &[PieceName::Pawn]) || attacker_at(pawn_rank, file + 1, &[PieceName::Pawn]) {
+        return true;
+    }
+
+    let steps = |steps: &[(i32, i32)], name: PieceName| {
+        steps.iter().any(|(ranks, files)| attacker_at(rank + ranks, file + files, &[name]))
+    };
+    if steps(&KNIGHT_STEPS, PieceName::Knight) || steps(&KING_STEPS, PieceName::King) {
+        return true;
+    }
+
+    // Sliding pieces attack along a line until it's blocked
+    let slides = |directions: &[(i32, i32)], names: &[PieceName]| {
+        directions.iter().any(|(ranks, files)| {
+            let (mut rank, mut file) = (rank + ranks, file + files);
+            while (0..8).contains(&rank) && (0..8).contains(&file) {
+                if state.squares[(rank * 8 + file) as usize].is_some() {
+                    return attacker_at(rank, file, names);
+                }
+                rank += ranks;
+                file += files;
+            }
+            false
+        })
+    };
+    slides(&ROOK_DIRECTIONS, &[PieceName::Rook, PieceName::Queen])
+        || slides(&BISHOP_DIRECTIONS, &[PieceName::Bishop, PieceName::Queen])
+}
+
+// Whether the side to move's king is attacked
+pub fn is_in_check(state: &GameState) -> bool {
+    let mover = state.to_move;
+    let king = (0..64).find(|&square| match state.squares[square] {
+        Some(piece) => piece.color == mover && matches!(piece.name, PieceName::King),
+        None => false,
+    });
+    let opponent = match mover {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    king.is_some_and(|square| is_attacked(state, square, opponent))
+}
+
+// Whether a decision took one of the opponent's pieces
+pub fn is_capture(before: &GameState, after: &GameState) -> bool {
+    let occupied = |state: &GameState| state.squares.iter().filter(|square| square.is_some()).count();
+    occupied(after) < occupied(before)
+}
+
+// Whether a decision promoted one of the mover's pawns
+pub fn is_promotion(before: &GameState, after: &GameState) -> bool {
+    let mover = before.to_move;
+    let pawns = |state: &GameState| {
+        state.squares.iter().flatten()
+            .filter(|piece| piece.color == mover && matches!(piece.name, PieceName::Pawn))
+            .count()
+    };
+    pawns(after) < pawns(before)
+}
+
+#[test]
+fn attacks_test() {
+    use crate::environment::ChessEnvironment;
+    use crate::notation::resolve_coordinate_move;
+
+    // Scholar's mate has the black king in check from the queen
+    let environment = ChessEnvironment::from_fen(
+        "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4"
+    ).unwrap();
+    assert!(is_in_check(&environment.state));
+    assert!(!is_in_check(&GameState::new()));
+
+    // Pieces block sliding attacks
+    let state = ChessEnvironment::from_fen("4k3/8/8/8/8/8/4P3/4R1K1 w - - 0 1").unwrap().state;
+    assert!(!is_attacked(&state, 60, Color::White));
+    assert!(!is_attacked(&state, 28, Color::White));
+    assert!(is_attacked(&state, 0, Color::White));
+    assert!(is_attacked(&state, 19, Color::White));
+
+    let state = ChessEnvironment::from_fen("4k3/1P6/8/8/3p4/4P3/8/4K3 w - - 0 1").unwrap().state;
+    assert!(is_capture(&state, &resolve_coordinate_move(&state, "e3d4").unwrap()));
+    assert!(!is_capture(&state, &resolve_coordinate_move(&state, "e3e4").unwrap()));
+    assert!(is_promotion(&state, &resolve_coordinate_move(&state, "b7b8q").unwrap()));
+}
@@ -0,0 +1,290 @@
+
+use chess_engine::*;
+use crate::notation::{parse_square, piece_from_letter, piece_index, square_name};
+use super::attacks::is_attacked;
+
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    WrongRankLength(usize),
+    UnknownPiece(char),
+    WrongKingCount { white: usize, black: usize },
+    TooManyPieces(&'static str),
+    PawnOnBackRank(String),
+    KingsAdjacent,
+    InvalidSideToMove(String),
+    OpponentInCheck,
+    InvalidCastlingRights(String),
+    InconsistentCastlingRights(char),
+    InvalidEnPassantSquare(String),
+    InvalidClock(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => write!(f, "expected 4 or 6 fields, found {}", count),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::WrongRankLength(rank) => write!(f, "rank {} does not describe 8 squares", rank),
+            FenError::UnknownPiece(letter) => write!(f, "unknown piece '{}'", letter),
+            FenError::WrongKingCount { white, black } => {
+                write!(f, "each side needs one king, found {} white and {} black", white, black)
+            },
+            FenError::TooManyPieces(color) => write!(f, "{} has more pieces than a game allows", color),
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on back rank at {}", square),
+            FenError::KingsAdjacent => write!(f, "kings cannot stand next to each other"),
+            FenError::InvalidSideToMove(side) => write!(f, "side to move must be w or b, found {}", side),
+            FenError::OpponentInCheck => write!(f, "the side that just moved cannot be left in check"),
+            FenError::InvalidCastlingRights(rights) => write!(f, "invalid castling rights {}", rights),
+            FenError::InconsistentCastlingRights(right) => {
+                write!(f, "castling right {} needs the king and rook on their starting squares", right)
+            },
+            FenError::InvalidEnPassantSquare(square) => write!(f, "invalid en passant square {}", square),
+            FenError::InvalidClock(clock) => write!(f, "invalid move clock {}", clock),
+        }
+    }
+}
+
+// The result of reading a FEN record: the position,
+// and the two move counters that GameState doesn't hold.
+pub struct FenRecord {
+    pub state: GameState,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+// Read a position in Forsyth-Edwards Notation, rejecting
+// any record that could not arise in a legal game. The
+// move counters are optional, defaulting to "0 1".
+pub fn parse_fen(fen: &str) -> Result<FenRecord, FenError> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() != 4 && fields.len() != 6 {
+        return Err(FenError::WrongFieldCount(fields.len()));
+    }
+
+    let mut state = GameState::with_placements(vec![]);
+
+    // Placement is listed from the eighth rank down
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+
+    for (row, rank_text) in ranks.iter().enumerate() {
+        let rank = 7 - row;
+        let mut file = 0;
+        for letter in rank_text.chars() {
+            if let Some(empty) = letter.to_digit(10) {
+                file += empty as usize;
+                continue;
+            }
+
+            let name = piece_from_letter(letter).ok_or(FenError::UnknownPiece(letter))?;
+            let color = match letter.is_ascii_uppercase() {
+                true => Color::White,
+                false => Color::Black,
+            };
+            if file < 8 {
+                state.squares[rank * 8 + file] = Some(Piece { color, name });
+            }
+            file += 1;
+        }
+
+        if file != 8 {
+            return Err(FenError::WrongRankLength(rank + 1));
+        }
+    }
+
+    validate_placement(&state)?;
+
+    state.to_move = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        side => return Err(FenError::InvalidSideToMove(side.to_string())),
+    };
+
+    // The side that just moved can't have left its own king attacked
+    let (opponent, mover) = match state.to_move {
+        Color::White => (Color::Black, Color::White),
+        Color::Black => (Color::White, Color::Black),
+    };
+    let opponent_king = (0..64).find(|&index| match state.squares[index] {
+        Some(piece) => piece.color == opponent && matches!(piece.name, PieceName::King),
+        None => false,
+    });
+    if opponent_king.is_some_and(|square| is_attacked(&state, square, mover)) {
+        return Err(FenError::OpponentInCheck);
+    }
+
+    let castling = fields[2];
+    if castling != "-" {
+        for right in castling.chars() {
+            let (flag, king, rook, color) = match right {
+                'K' => (&mut state.white_can_castle_kingside, 4, 7, Color::White),
+                'Q' => (&mut state.white_can_castle_queenside, 4, 0, Color::White),
+                'k' => (&mut state.black_can_castle_kingside, 60, 63, Color::Black),
+                'q' => (&mut state.black_can_castle_queenside, 60, 56, Color::Black),
+                _ => return Err(FenError::InvalidCastlingRights(castling.to_string())),
+            };
+            if *flag {
+                return Err(FenError::InvalidCastlingRights(castling.to_string()));
+            }
+            *flag = true;
+
+            let stands_on = |index: usize, expected: PieceName| match state.squares[index] {
+                Some(piece) => piece.color == color && piece_index(piece.name) == piece_index(expected),
+                None => false,
+            };
+            if !stands_on(king, PieceName::King) || !stands_on(rook, PieceName::Rook) {
+                return Err(FenError::InconsistentCastlingRights(right));
+            }
+        }
+    }
+
+    if fields[3] != "-" {
+        let invalid = || FenError::InvalidEnPassantSquare(fields[3].to_string());
+        let target = parse_square(fields[3]).ok_or_else(invalid)?;
+
+        // The pawn that just advanced two squares stands in front
+        // of the target square, and the square it came from is empty.
+        let target_rank = match state.to_move {
+            Color::White => 5,
+            Color::Black => 2,
+        };
+        if target / 8 != target_rank {
+            return Err(invalid());
+        }
+
+        let (pawn, origin, pawn_color) = match state.to_move {
+            Color::White => (target - 8, target + 8, Color::Black),
+            Color::Black => (target + 8, target - 8, Color::White),
+        };
+
+        let pawn_in_place = match state.squares[pawn] {
+            Some(piece) => piece.color == pawn_color && matches!(piece.name, PieceName::Pawn),
+            None => false,
+        };
+        if !pawn_in_place || state.squares[target].is_some() || state.squares[origin].is_some() {
+            return Err(invalid());
+        }
+        state.en_passant_square = Some(target);
+    }
+
+    let (halfmove_clock, fullmove_number) = match fields.len() {
+        6 => {
+            let invalid = |clock: &str| FenError::InvalidClock(clock.to_string());
+            let halfmove_clock: u32 = fields[4].parse().map_err(|_| invalid(fields[4]))?;
+            let fullmove_number: u32 = fields[5].parse().map_err(|_| invalid(fields[5]))?;
+            if fullmove_number == 0 {
+                return Err(invalid(fields[5]));
+            }
+            (halfmove_clock, fullmove_number)
+        },
+        _ => (0, 1),
+    };
+
+    Ok(FenRecord {
+        state,
+        halfmove_clock,
+        fullmove_number,
+    })
+}
+
+fn validate_placement(state: &GameState) -> Result<(), FenError> {
+    let mut kings = [vec![], vec![]];
+    let mut counts = [[0; 6]; 2];
+
+    for index in 0..64 {
+        if let Some(piece) = state.squares[index] {
+            let side = match piece.color {
+                Color::White => 0,
+                Color::Black => 1,
+            };
+            counts[side][piece_index(piece.name)] += 1;
+
+            match piece.name {
+                PieceName::King => kings[side].push(index),
+                PieceName::Pawn if index / 8 == 0 || index / 8 == 7 => {
+                    return Err(FenError::PawnOnBackRank(square_name(index)));
+                },
+                _ => (),
+            }
+        }
+    }
+
+    if kings[0].len() != 1 || kings[1].len() != 1 {
+        return Err(FenError::WrongKingCount {
+            white: kings[0].len(),
+            black: kings[1].len(),
+        });
+    }
+
+    let (white_king, black_king) = (kings[0][0] as i32, kings[1][0] as i32);
+    if (white_king / 8 - black_king / 8).abs() <= 1 && (white_king % 8 - black_king % 8).abs() <= 1 {
+        return Err(FenError::KingsAdjacent);
+    }
+
+    // Every piece beyond the starting set must have been
+    // promoted from a pawn that is no longer on the board.
+    for (side, color) in [(0, "white"), (1, "black")].iter() {
+        let count = counts[*side];
+        let pawns = count[piece_index(PieceName::Pawn)];
+        let starting_set = [
+            (PieceName::Knight, 2),
+            (PieceName::Bishop, 2),
+            (PieceName::Rook, 2),
+            (PieceName::Queen, 1),
+        ];
+        let promotions: i32 = starting_set.iter()
+            .map(|(name, initial)| std::cmp::max(0, count[piece_index(*name)] - initial))
+            .sum();
+
+        if pawns > 8 || pawns + promotions > 8 || count.iter().sum::<i32>() > 16 {
+            return Err(FenError::TooManyPieces(color));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn parse_fen_test() {
+    let record = parse_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    assert!(record.state.to_move == Color::Black);
+    assert_eq!(record.state.en_passant_square, Some(20));
+    assert!(record.state.white_can_castle_kingside);
+    assert!(record.state.black_can_castle_queenside);
+    assert!(record.state.squares[12].is_none());
+    assert!(record.state.squares[28].is_some());
+
+    let record = parse_fen("8/8/4k3/8/8/3BK3/8/8 w - - 12 40").unwrap();
+    assert_eq!(record.halfmove_clock, 12);
+    assert_eq!(record.fullmove_number, 40);
+    assert!(!record.state.white_can_castle_kingside);
+
+    let record = parse_fen("8/8/4k3/8/8/4K3/8/8 w - -").unwrap();
+    assert_eq!(record.halfmove_clock, 0);
+    assert_eq!(record.fullmove_number, 1);
+}
+
+#[test]
+fn parse_fen_error_test() {
+    let error = |fen: &str| parse_fen(fen).err().unwrap();
+
+    assert_eq!(error("8/8/8/8 w - - 0 1"), FenError::WrongRankCount(4));
+    assert_eq!(error("8/8/4k3/8/8/4K3/8 w - 0 1"), FenError::WrongFieldCount(5));
+    assert_eq!(error("8/8/4k3/8/8/4K3/8/7 w - - 0 1"), FenError::WrongRankLength(1));
+    assert_eq!(error("8/8/4k3/8/8/4X3/8/8 w - - 0 1"), FenError::UnknownPiece('X'));
+    assert_eq!(error("8/8/8/8/8/4K3/8/8 w - - 0 1"), FenError::WrongKingCount { white: 1, black: 0 });
+    assert_eq!(error("8/8/8/8/4k3/4K3/8/8 w - - 0 1"), FenError::KingsAdjacent);
+    assert_eq!(error("P7/8/4k3/8/8/4K3/8/8 w - - 0 1"), FenError::PawnOnBackRank("a8".to_string()));
+    assert_eq!(error("QQQQQQQQ/QQ6/4k3/8/8/4K3/8/8 w - - 0 1"), FenError::TooManyPieces("white"));
+    assert_eq!(error("8/8/4k3/8/8/4K3/8/8 x - - 0 1"), FenError::InvalidSideToMove("x".to_string()));
+    assert_eq!(error("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1"), FenError::OpponentInCheck);
+    assert!(parse_fen("4k3/4R3/8/8/8/8/8/4K3 b - - 0 1").is_ok());
+    assert_eq!(error("4k3/8/8/8/8/8/8/4K2R w KK - 0 1"), FenError::InvalidCastlingRights("KK".to_string()));
+    assert_eq!(error("8/8/4k3/8/8/8/8/4K3 w K - 0 1"), FenError::InconsistentCastlingRights('K'));
+    assert_eq!(error("8/8/4k3/8/8/4K3/8/8 w - e6 0 1"), FenError::InvalidEnPassantSquare("e6".to_string()));
+    assert_eq!(error("8/8/4k3/8/8/4K3/8/8 w - - x 1"), FenError::InvalidClock("x".to_string()));
+}
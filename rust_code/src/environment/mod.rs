@@ -1,50 +1,377 @@
 
+use std::sync::Arc;
 use chess_engine::*;
-use rand::Rng;
+use rand::{Rng, RngCore};
+use crate::vectors::numeralize_gamestate;
+use crate::random::SeededRng;
+use crate::zobrist::{update_key, zobrist_key};
 
+mod reward;
+mod fen;
+mod attacks;
+pub use reward::*;
+pub use fen::*;
+pub use attacks::*;
 
+
+#[derive(Clone)]
 pub struct ChessEnvironment {
     pub state: GameState,
+    pub halfmove_clock: u32,
+    pub reward_function: RewardFunction,
+    initial_state: GameState,
+    initial_halfmove_clock: u32,
+    initial_fullmove_number: u32,
+    initial_key: u64,
+    // The most recent ply, which leads back through every
+    // ply before it, for replaying and detecting repetition
+    last_ply: Option<Arc<PlyRecord>>,
+    plies_played: usize,
+    undone: Vec<GameState>,
+    // Random decisions made by the environment are drawn from here
+    rng: SeededRng,
+}
+
+// A ply as the environment records it, linked to the ply before
+// it. Branches share every ply they have in common with the
+// environment they came from, so branching doesn't copy the game.
+struct PlyRecord {
+    state: GameState,
+    halfmove_clock: u32,
+    // The zobrist key of the position reached
+    key: u64,
+    previous: Option<Arc<PlyRecord>>,
 }
 
+// A single half-move of the game: the state that was
+// reached, and the algebraic notation of the action that
+// produced it, when that action was legal.
+#[derive(Clone)]
+pub struct Ply {
+    pub action: Option<String>,
+    pub state: GameState,
+}
+
+// Diagnostic information returned alongside each step,
+// describing the environment after the move was made.
+#[derive(Copy, Clone)]
+pub struct StepInfo {
+    pub mover: Color,
+    pub terminal_state: Option<TerminalState>,
+    pub halfmove_clock: u32,
+    pub repetitions: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TerminalState {
-    Win, 
+    Win,
     Loss,
-    Draw,
+    Draw(DrawReason),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawReason {
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+    // The game hasn't ended, such as when it was cut off by
+    // a turn limit. It's scored as a draw, but isn't one.
+    Unfinished,
 }
 
 impl ChessEnvironment {
     pub fn new() -> ChessEnvironment {
+        ChessEnvironment::from_state(GameState::new())
+    }
+
+    pub fn from_state(state: GameState) -> ChessEnvironment {
         ChessEnvironment {
-            state: GameState::new(),
+            state,
+            halfmove_clock: 0,
+            reward_function: RewardFunction::TerminalOnly,
+            initial_state: state,
+            initial_halfmove_clock: 0,
+            initial_fullmove_number: 1,
+            initial_key: zobrist_key(&state),
+            last_ply: None,
+            plies_played: 0,
+            undone: vec![],
+            rng: SeededRng::unseeded(),
+        }
+    }
+
+    // Start from a position given in Forsyth-Edwards Notation,
+    // such as an endgame to train on, or a position to debug.
+    pub fn from_fen(fen: &str) -> Result<ChessEnvironment, FenError> {
+        let record = parse_fen(fen)?;
+        let mut environment = ChessEnvironment::from_state(record.state);
+        environment.halfmove_clock = record.halfmove_clock;
+        environment.initial_halfmove_clock = record.halfmove_clock;
+        environment.initial_fullmove_number = record.fullmove_number;
+        Ok(environment)
+    }
+
+    pub fn with_reward_function(mut self, reward_function: RewardFunction) -> ChessEnvironment {
+        self.reward_function = reward_function;
+        self
+    }
+
+    // Make the environment's random decisions repeatable
+    pub fn with_seed(mut self, seed: u64) -> ChessEnvironment {
+        self.rng = SeededRng::new(seed);
+        self
+    }
+
+    // Return the environment to the position it was
+    // created with, and observe that position.
+    pub fn reset(&mut self) -> GameState {
+        self.state = self.initial_state;
+        self.halfmove_clock = self.initial_halfmove_clock;
+        self.last_ply = None;
+        self.plies_played = 0;
+        self.undone.clear();
+        self.state
+    }
+
+    // Apply an action on behalf of the side to move, returning
+    // the resulting observation, the reward earned by the mover,
+    // whether the game is over, and diagnostic information.
+    pub fn step(&mut self, action: GameState) -> (GameState, f32, bool, StepInfo) {
+        let mover = self.state.to_move;
+        if self.is_terminated() {
+            return (self.state, 0.0, true, self.step_info(mover));
         }
+
+        let previous = self.state;
+        self.apply_change(action);
+        let reward = self.reward_function.reward(&previous, self, mover);
+
+        (self.state, reward, self.is_terminated(), self.step_info(mover))
+    }
+
+    fn step_info(&self, mover: Color) -> StepInfo {
+        let terminal_state = match self.is_terminated() {
+            true => Some(self.terminal_state(mover)),
+            false => None,
+        };
+
+        StepInfo {
+            mover,
+            terminal_state,
+            halfmove_clock: self.halfmove_clock,
+            repetitions: self.repetitions(),
+        }
+    }
+
+    // Create a copy of this environment with a change
+    // applied, keeping the history needed to detect
+    // draws further down the line. The history is shared
+    // rather than copied, so branching is cheap enough for
+    // every position a search visits.
+    pub fn branch(&self, state: GameState) -> ChessEnvironment {
+        let mut environment = ChessEnvironment {
+            state: self.state,
+            halfmove_clock: self.halfmove_clock,
+            reward_function: self.reward_function.clone(),
+            initial_state: self.initial_state,
+            initial_halfmove_clock: self.initial_halfmove_clock,
+            initial_fullmove_number: self.initial_fullmove_number,
+            initial_key: self.initial_key,
+            last_ply: self.last_ply.clone(),
+            plies_played: self.plies_played,
+            undone: vec![],
+            rng: SeededRng::new(0),
+        };
+        environment.apply_change(state);
+
+        // Each branch draws from a stream of its own, so
+        // siblings don't make the same random decisions
+        environment.rng = SeededRng::stream(self.rng.clone().next_u64(), environment.position_key());
+        environment
     }
 
     // Set the environment state to a given state,
     // presumably (but not necessarily) one of the
     // legal next states based on the current state.
     pub fn apply_change(&mut self, state: GameState) {
+        self.undone.clear();
+        self.record_change(state);
+    }
+
+    fn record_change(&mut self, state: GameState) {
+        if resets_halfmove_clock(&self.state, &state) {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        let key = update_key(self.position_key(), &self.state, &state);
+        self.last_ply = Some(Arc::new(PlyRecord {
+            state,
+            halfmove_clock: self.halfmove_clock,
+            key,
+            previous: self.last_ply.take(),
+        }));
+        self.plies_played += 1;
         self.state = state;
     }
 
+    // Take back the most recent ply, returning false
+    // if there is nothing left to take back.
+    pub fn undo(&mut self) -> bool {
+        let ply = match self.last_ply.take() {
+            Some(ply) => ply,
+            None => return false,
+        };
+
+        self.undone.push(ply.state);
+        self.last_ply = ply.previous.clone();
+        self.plies_played -= 1;
+
+        let (previous_state, previous_clock) = match &self.last_ply {
+            Some(ply) => (ply.state, ply.halfmove_clock),
+            None => (self.initial_state, self.initial_halfmove_clock),
+        };
+        self.state = previous_state;
+        self.halfmove_clock = previous_clock;
+        true
+    }
+
+    // Replay the most recently undone ply, returning false
+    // if nothing has been undone since the last change.
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            Some(state) => {
+                self.record_change(state);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Every ply played since the initial state, in order.
+    pub fn history(&self) -> Vec<Ply> {
+        let mut states: Vec<GameState> = self.plies_newest_first().map(|ply| ply.state).collect();
+        states.reverse();
+
+        let mut previous = self.initial_state;
+        states.into_iter().map(|state| {
+            let action = algebraic_notation(&previous, &state);
+            previous = state;
+            Ply { action, state }
+        }).collect()
+    }
+
+    pub fn plies_played(&self) -> usize {
+        self.plies_played
+    }
+
+    fn plies_newest_first(&self) -> impl Iterator<Item = &PlyRecord> {
+        std::iter::successors(self.last_ply.as_deref(), |ply| ply.previous.as_deref())
+    }
+
+    pub fn initial_state(&self) -> GameState {
+        self.initial_state
+    }
+
+    // The number of the current full move, which starts at 1
+    // and is incremented after each of black's moves.
+    pub fn fullmove_number(&self) -> u32 {
+        let black_moved_first = self.initial_state.to_move == Color::Black;
+        let plies = self.plies_played as u32 + if black_moved_first { 1 } else { 0 };
+        self.initial_fullmove_number + plies / 2
+    }
+
+    // The current position in Forsyth-Edwards Notation,
+    // including the move counters.
+    pub fn fen(&self) -> String {
+        format!("{} {} {}", position_fields(&self.state), self.halfmove_clock, self.fullmove_number())
+    }
+
+    pub fn initial_fen(&self) -> String {
+        format!(
+            "{} {} {}",
+            position_fields(&self.initial_state),
+            self.initial_halfmove_clock,
+            self.initial_fullmove_number,
+        )
+    }
+
     // Set the environment state to a random available
     // next state.
     pub fn apply_change_randomly(&mut self) {
-        if !self.is_terminated() {
-            let decisions = self.available_decisions();
-            let mut rng = rand::thread_rng();
-            let random_index = rng.gen_range(0, decisions.len());
-            self.state = decisions[random_index];
+        if let Some(decision) = self.random_decision() {
+            self.apply_change(decision);
         }
     }
 
+    // Choose one of the available next states at random,
+    // if the game is not over.
+    pub fn random_decision(&mut self) -> Option<GameState> {
+        if self.is_terminated() {
+            return None;
+        }
+
+        let decisions = self.available_decisions();
+        let random_index = self.rng.gen_range(0, decisions.len());
+        Some(decisions[random_index])
+    }
+
+    // Choose a random next state with a particular
+    // random number generator, such as a seeded one.
+    pub fn random_decision_using<R: Rng>(&self, rng: &mut R) -> Option<GameState> {
+        if self.is_terminated() {
+            return None;
+        }
+
+        let decisions = self.available_decisions();
+        let random_index = rng.gen_range(0, decisions.len());
+        Some(decisions[random_index])
+    }
+
 
     pub fn available_decisions(&self) -> Vec<GameState> {
         legal_next_states(&self.state)
     }
 
     pub fn is_terminated(&self) -> bool {
-        is_checkmate(&self.state) || is_stalemate(&self.state)
+        is_checkmate(&self.state) || self.draw_reason().is_some()
+    }
+
+    // The zobrist key of the current position
+    pub fn position_key(&self) -> u64 {
+        match &self.last_ply {
+            Some(ply) => ply.key,
+            None => self.initial_key,
+        }
+    }
+
+    // The number of times the current position has
+    // occurred, including the current occurrence.
+    pub fn repetitions(&self) -> usize {
+        let current = self.position_key();
+        self.plies_newest_first()
+            .map(|ply| ply.key)
+            .chain(std::iter::once(self.initial_key))
+            .filter(|key| *key == current)
+            .count()
+    }
+
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        if is_stalemate(&self.state) {
+            return Some(DrawReason::Stalemate);
+        }
+        if has_insufficient_material(&self.state) {
+            return Some(DrawReason::InsufficientMaterial);
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(DrawReason::FiftyMoveRule);
+        }
+        if self.repetitions() >= 3 {
+            return Some(DrawReason::ThreefoldRepetition);
+        }
+        None
     }
 
     pub fn terminal_state(&self, perspective: Color) -> TerminalState {
@@ -54,9 +381,258 @@ impl ChessEnvironment {
                 true => TerminalState::Loss,
             };
         }
-        TerminalState::Draw
+        TerminalState::Draw(self.draw_reason().unwrap_or(DrawReason::Unfinished))
+    }
+}
+
+impl Default for ChessEnvironment {
+    fn default() -> ChessEnvironment {
+        ChessEnvironment::new()
+    }
+}
+
+// Find the notation of the legal action that changes
+// one state into another, if there is one.
+pub fn algebraic_notation(before: &GameState, after: &GameState) -> Option<String> {
+    let target = numeralize_gamestate(after);
+    legal_actions(before).iter()
+        .find(|action| numeralize_gamestate(&action.apply(before)) == target)
+        .map(|action| action.as_algebraic_notation(before))
+}
+
+// The first four fields of a FEN record, which describe
+// placement, side to move, castling rights and the en
+// passant square, leaving out the move counters.
+fn position_fields(state: &GameState) -> String {
+    fen_notation(state)
+        .split_whitespace()
+        .take(4)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// The halfmove clock is reset whenever a pawn is moved,
+// or a piece is captured.
+fn resets_halfmove_clock(before: &GameState, after: &GameState) -> bool {
+    let occupied = |state: &GameState| state.squares.iter().filter(|s| s.is_some()).count();
+    if occupied(after) < occupied(before) {
+        return true;
+    }
+
+    (0..64).any(|index| match before.squares[index] {
+        Some(piece) => {
+            piece.color == before.to_move
+                && matches!(piece.name, PieceName::Pawn)
+                && after.squares[index].is_none()
+        },
+        None => false,
+    })
+}
+
+// Neither side can possibly deliver checkmate with only
+// kings, a single minor piece, or bishops that all stand
+// on squares of the same color.
+pub fn has_insufficient_material(state: &GameState) -> bool {
+    let mut minor_pieces = 0;
+    let mut bishop_square_colors = vec![];
+
+    for index in 0..64 {
+        if let Some(piece) = state.squares[index] {
+            match piece.name {
+                PieceName::King => (),
+                PieceName::Knight => minor_pieces += 1,
+                PieceName::Bishop => {
+                    minor_pieces += 1;
+                    bishop_square_colors.push((index / 8 + index % 8) % 2);
+                },
+                _ => return false,
+            }
+        }
+    }
+
+    if minor_pieces <= 1 {
+        return true;
+    }
+
+    bishop_square_colors.len() == minor_pieces
+        && bishop_square_colors.iter().all(|c| *c == bishop_square_colors[0])
+}
+
+
+// White to move, with Ra8 mate, and the decision that mates
+#[cfg(test)]
+pub fn rook_mate_in_one() -> (ChessEnvironment, GameState) {
+    let environment = ChessEnvironment::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+    let mate = crate::notation::resolve_coordinate_move(&environment.state, "a1a8").unwrap();
+    (environment, mate)
+}
+
+#[cfg(test)]
+fn with_pieces(pieces: Vec<(usize, Piece)>) -> GameState {
+    let mut state = GameState::with_placements(vec![]);
+    for (index, piece) in pieces {
+        state.squares[index] = Some(piece);
     }
+    state
 }
 
+#[cfg(test)]
+fn move_piece(state: &GameState, from: usize, to: usize) -> GameState {
+    let mut next = *state;
+    next.squares[to] = next.squares[from];
+    next.squares[from] = None;
+    next.to_move = match state.to_move {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    next
+}
+
+#[test]
+fn insufficient_material_test() {
+    let white_king = (4, Piece { color: Color::White, name: PieceName::King });
+    let black_king = (60, Piece { color: Color::Black, name: PieceName::King });
+
+    let state = with_pieces(vec![white_king, black_king]);
+    assert!(has_insufficient_material(&state));
+
+    let bishop = (2, Piece { color: Color::White, name: PieceName::Bishop });
+    let state = with_pieces(vec![white_king, black_king, bishop]);
+    assert!(has_insufficient_material(&state));
+
+    // Both bishops stand on dark squares
+    let other_bishop = (61, Piece { color: Color::Black, name: PieceName::Bishop });
+    let state = with_pieces(vec![white_king, black_king, bishop, other_bishop]);
+    assert!(has_insufficient_material(&state));
+
+    let other_bishop = (58, Piece { color: Color::Black, name: PieceName::Bishop });
+    let state = with_pieces(vec![white_king, black_king, bishop, other_bishop]);
+    assert!(!has_insufficient_material(&state));
+
+    let rook = (0, Piece { color: Color::White, name: PieceName::Rook });
+    let state = with_pieces(vec![white_king, black_king, rook]);
+    assert!(!has_insufficient_material(&state));
+}
+
+#[test]
+fn threefold_repetition_test() {
+    let mut environment = ChessEnvironment::new();
+    assert_eq!(
+        environment.terminal_state(Color::White),
+        TerminalState::Draw(DrawReason::Unfinished),
+    );
+
+    for _ in 0..2 {
+        assert!(!environment.is_terminated());
+        environment.apply_change(move_piece(&environment.state, 6, 21));
+        environment.apply_change(move_piece(&environment.state, 62, 45));
+        environment.apply_change(move_piece(&environment.state, 21, 6));
+        environment.apply_change(move_piece(&environment.state, 45, 62));
+    }
+
+    assert_eq!(environment.repetitions(), 3);
+    assert_eq!(environment.halfmove_clock, 8);
+    assert_eq!(
+        environment.terminal_state(Color::White),
+        TerminalState::Draw(DrawReason::ThreefoldRepetition),
+    );
+
+    // A branch sees the history it came from,
+    // without changing that history
+    environment.undo();
+    let branch = environment.branch(move_piece(&environment.state, 45, 62));
+    assert_eq!(branch.repetitions(), 3);
+    assert_eq!(branch.plies_played(), 8);
+    assert_eq!(environment.plies_played(), 7);
+    assert!(!environment.is_terminated());
+}
+
+#[test]
+fn step_reward_test() {
+    let white_king = (4, Piece { color: Color::White, name: PieceName::King });
+    let black_king = (60, Piece { color: Color::Black, name: PieceName::King });
+    let rook = (0, Piece { color: Color::White, name: PieceName::Rook });
+    let black_rook = (8, Piece { color: Color::Black, name: PieceName::Rook });
+    let state = with_pieces(vec![white_king, black_king, rook, black_rook]);
+
+    let mut environment = ChessEnvironment::from_state(state)
+        .with_reward_function(RewardFunction::MaterialDelta);
+
+    // Capturing the rook leaves white a rook ahead
+    let (observation, reward, done, info) = environment.step(move_piece(&state, 0, 8));
+    assert!(observation.squares[0].is_none());
+    assert!(reward > 0.0);
+    assert!(!done);
+    assert!(info.mover == Color::White);
+    assert_eq!(info.halfmove_clock, 0);
 
+    environment.reset();
+    assert_eq!(environment.repetitions(), 1);
+    assert!(environment.state.squares[0].is_some());
+}
+
+#[test]
+fn undo_redo_test() {
+    let mut environment = ChessEnvironment::new();
+    let start = numeralize_gamestate(&environment.state);
+    assert!(!environment.undo());
+
+    environment.apply_change(move_piece(&environment.state, 6, 21));
+    let after_knight = numeralize_gamestate(&environment.state);
+    environment.apply_change(move_piece(&environment.state, 52, 36));
+    assert_eq!(environment.history().len(), 2);
+    assert!(environment.history()[0].action.is_some());
+
+    assert!(environment.undo());
+    assert_eq!(numeralize_gamestate(&environment.state)[..], after_knight[..]);
+    assert_eq!(environment.halfmove_clock, 1);
+
+    assert!(environment.undo());
+    assert_eq!(numeralize_gamestate(&environment.state)[..], start[..]);
+    assert_eq!(environment.history().len(), 0);
+
+    assert!(environment.redo());
+    assert!(environment.redo());
+    assert!(!environment.redo());
+    assert_eq!(environment.history().len(), 2);
+    assert_eq!(environment.halfmove_clock, 0);
+
+    // Making a new change discards anything left to redo
+    environment.undo();
+    environment.apply_change(move_piece(&environment.state, 51, 35));
+    assert!(!environment.redo());
+}
+
+#[test]
+fn from_fen_test() {
+    let mut environment = ChessEnvironment::from_fen("8/8/4k3/8/8/4K3/4R3/8 b - - 7 30").unwrap();
+    assert!(environment.state.to_move == Color::Black);
+    assert_eq!(environment.halfmove_clock, 7);
+    assert_eq!(environment.fullmove_number(), 30);
+
+    environment.apply_change(move_piece(&environment.state, 44, 45));
+    assert_eq!(environment.halfmove_clock, 8);
+    assert_eq!(environment.fullmove_number(), 31);
+    assert!(environment.fen().ends_with(" 8 31"));
+
+    environment.reset();
+    assert_eq!(environment.halfmove_clock, 7);
+    assert_eq!(environment.initial_fen(), environment.fen());
+
+    assert!(ChessEnvironment::from_fen("not a position").is_err());
+}
+
+#[test]
+fn fifty_move_rule_test() {
+    let white_king = (4, Piece { color: Color::White, name: PieceName::King });
+    let black_king = (60, Piece { color: Color::Black, name: PieceName::King });
+    let rook = (0, Piece { color: Color::White, name: PieceName::Rook });
+    let mut environment = ChessEnvironment::from_state(with_pieces(vec![white_king, black_king, rook]));
+
+    environment.halfmove_clock = 99;
+    assert!(environment.draw_reason().is_none());
+
+    environment.apply_change(move_piece(&environment.state, 0, 8));
+    assert_eq!(environment.draw_reason(), Some(DrawReason::FiftyMoveRule));
+}
 
@@ -0,0 +1,71 @@
+
+use std::sync::Arc;
+use chess_engine::*;
+use super::*;
+
+// Custom rewards are shared between threads along with the
+// environment, so engine front ends can search in the background.
+pub type CustomReward = Arc<dyn Fn(&GameState, &ChessEnvironment, Color) -> f32 + Send + Sync>;
+
+// Determines the reward handed out by ChessEnvironment::step,
+// always from the perspective of the side that made the move.
+#[derive(Clone)]
+pub enum RewardFunction {
+    // Reward only the end of the game: 1.0 for delivering
+    // checkmate, and 0.0 for everything else.
+    TerminalOnly,
+
+    // Reward the change in material balance caused by the
+    // move, in addition to the terminal reward.
+    MaterialDelta,
+
+    // Called with the state before the move, the environment
+    // after it, and the color of the side that moved.
+    Custom(CustomReward),
+}
+
+impl RewardFunction {
+    pub fn reward(&self, previous: &GameState, environment: &ChessEnvironment, mover: Color) -> f32 {
+        let terminal_reward = match environment.is_terminated() {
+            true => environment.terminal_state(mover).reward(),
+            false => 0.0,
+        };
+
+        match self {
+            RewardFunction::TerminalOnly => terminal_reward,
+            RewardFunction::MaterialDelta => {
+                let before = material_balance(previous, mover);
+                let after = material_balance(&environment.state, mover);
+                terminal_reward + after - before
+            },
+            RewardFunction::Custom(function) => function(previous, environment, mover),
+        }
+    }
+}
+
+impl TerminalState {
+    pub fn reward(&self) -> f32 {
+        match self {
+            TerminalState::Win => 1.0,
+            TerminalState::Loss => -1.0,
+            TerminalState::Draw(_) => 0.0,
+        }
+    }
+}
+
+// The material advantage of one side, normalized
+// between -1.0 and 1.0.
+pub fn material_balance(state: &GameState, perspective: Color) -> f32 {
+    let (white_score, black_score) = relative_material_values(state);
+    let max_score = std::cmp::max(white_score, black_score) as f32;
+    if max_score == 0.0 {
+        return 0.0;
+    }
+
+    let value = (white_score as f32 - black_score as f32) / max_score;
+    match perspective {
+        Color::White => value,
+        Color::Black => -value,
+    }
+}
+
@@ -3,8 +3,26 @@ mod vectors;
 mod agent;
 mod environment;
 mod cli;
+mod pgn;
+mod notation;
+mod zobrist;
+mod training;
+mod profile;
+mod arena;
+mod random;
 
-pub use agent::ChessAgent;
-pub use environment::{ChessEnvironment, TerminalState};
+pub use agent::{ChessAgent, AgentConfig, Policy, MctsOptions, VisitDistribution, ExperienceStore, Recollection, UpdateRule};
+pub use agent::{Exploration, ExplorationSchedule};
+pub use agent::{Clock, SearchInfo, SearchLimits, MAX_SEARCH_DEPTH};
+pub use agent::{Bound, TableStatistics, TranspositionEntry, TranspositionOptions, TranspositionTable};
+pub use agent::{MoveOrdering, QuiescenceOptions};
+pub use environment::{ChessEnvironment, TerminalState, DrawReason, RewardFunction, StepInfo, FenError};
 pub use cli::*;
+pub use pgn::*;
+pub use notation::*;
+pub use zobrist::*;
+pub use training::*;
+pub use profile::*;
+pub use arena::*;
+pub use random::*;
 
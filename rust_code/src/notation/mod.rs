@@ -0,0 +1,265 @@
+
+use chess_engine::*;
+
+// A move described by the squares it connects, which is
+// how coordinate notations like UCI's "e2e4" see the board.
+#[derive(Copy, Clone)]
+pub struct CoordinateMove {
+    pub from: usize,
+    pub to: usize,
+    pub promotion: Option<PieceName>,
+}
+
+pub fn piece_index(name: PieceName) -> usize {
+    match name {
+        PieceName::Pawn => 0,
+        PieceName::Knight => 1,
+        PieceName::Bishop => 2,
+        PieceName::Rook => 3,
+        PieceName::Queen => 4,
+        PieceName::King => 5,
+    }
+}
+
+pub fn piece_from_letter(letter: char) -> Option<PieceName> {
+    match letter.to_ascii_uppercase() {
+        'P' => Some(PieceName::Pawn),
+        'N' => Some(PieceName::Knight),
+        'B' => Some(PieceName::Bishop),
+        'R' => Some(PieceName::Rook),
+        'Q' => Some(PieceName::Queen),
+        'K' => Some(PieceName::King),
+        _ => None,
+    }
+}
+
+pub fn piece_letter(name: PieceName) -> char {
+    match name {
+        PieceName::Pawn => 'p',
+        PieceName::Knight => 'n',
+        PieceName::Bishop => 'b',
+        PieceName::Rook => 'r',
+        PieceName::Queen => 'q',
+        PieceName::King => 'k',
+    }
+}
+
+// Squares are indexed from a1 = 0 to h8 = 63
+pub fn square_name(index: usize) -> String {
+    let file = (b'a' + (index % 8) as u8) as char;
+    let rank = (b'1' + (index / 8) as u8) as char;
+    format!("{}{}", file, rank)
+}
+
+pub fn parse_square(name: &str) -> Option<usize> {
+    let mut chars = name.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((rank as usize - '1' as usize) * 8 + (file as usize - 'a' as usize))
+}
+
+// Work out which piece moved between two states. Castling
+// is described by the king's move, and a promotion by the
+// piece the pawn became.
+pub fn describe_change(before: &GameState, after: &GameState) -> Option<CoordinateMove> {
+    let mover = before.to_move;
+    let belongs_to_mover = |square: Option<Piece>| match square {
+        Some(piece) => piece.color == mover,
+        None => false,
+    };
+
+    let vacated: Vec<usize> = (0..64)
+        .filter(|&i| belongs_to_mover(before.squares[i]) && !belongs_to_mover(after.squares[i]))
+        .collect();
+    let arrived: Vec<usize> = (0..64)
+        .filter(|&i| belongs_to_mover(after.squares[i]) && !belongs_to_mover(before.squares[i]))
+        .collect();
+
+    let (from, to) = match (vacated.len(), arrived.len()) {
+        (1, 1) => (vacated[0], arrived[0]),
+
+        // Castling moves both the king and a rook
+        (2, 2) => {
+            let is_king = |i: &&usize| matches!(before.squares[**i], Some(Piece { name: PieceName::King, .. }));
+            let from = *vacated.iter().find(is_king)?;
+            let to = *arrived.iter().find(|&&i| {
+                matches!(after.squares[i], Some(Piece { name: PieceName::King, .. }))
+            })?;
+            (from, to)
+        },
+        _ => return None,
+    };
+
+    let moved = before.squares[from]?.name;
+    let landed = after.squares[to]?.name;
+    let promotion = match piece_index(moved) == piece_index(landed) {
+        true => None,
+        false => Some(landed),
+    };
+
+    Some(CoordinateMove { from, to, promotion })
+}
+
+// Long algebraic notation as used by UCI, e.g. "e2e4" or "e7e8q"
+pub fn coordinate_notation(before: &GameState, after: &GameState) -> Option<String> {
+    let change = describe_change(before, after)?;
+    let mut notation = format!("{}{}", square_name(change.from), square_name(change.to));
+    if let Some(name) = change.promotion {
+        notation.push(piece_letter(name));
+    }
+    Some(notation)
+}
+
+// Find the legal next state described by a coordinate move
+pub fn resolve_coordinate_move(state: &GameState, notation: &str) -> Option<GameState> {
+    let notation = notation.trim();
+    if notation.len() < 4 || !notation.is_ascii() {
+        return None;
+    }
+
+    let from = parse_square(&notation[0..2])?;
+    let to = parse_square(&notation[2..4])?;
+    let promotion = match notation[4..].chars().next() {
+        Some(letter) => Some(piece_index(piece_from_letter(letter)?)),
+        None => None,
+    };
+
+    legal_next_states(state).into_iter().find(|next| {
+        match describe_change(state, next) {
+            Some(change) => {
+                change.from == from
+                    && change.to == to
+                    && change.promotion.map(piece_index) == promotion
+            },
+            None => false,
+        }
+    })
+}
+
+// Find the legal next state described by a move in Standard
+// Algebraic Notation. Check and annotation symbols are ignored,
+// and superfluous disambiguation is tolerated.
+pub fn resolve_san(state: &GameState, san: &str) -> Option<GameState> {
+    let san: String = san
+        .trim_end_matches(&['+', '#', '!', '?'][..])
+        .replace("e.p.", "")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != 'x' && *c != ':' && *c != '=')
+        .collect();
+
+    let castle_side = match san.replace("0", "O").as_str() {
+        "O-O" => Some(1),
+        "O-O-O" => Some(-1),
+        _ => None,
+    };
+
+    let mut chars: Vec<char> = match castle_side {
+        Some(_) => vec![],
+        None => san.replace("-", "").chars().collect(),
+    };
+
+    // Promotions are written as a trailing piece letter
+    let mut promotion = None;
+    if chars.len() > 2 {
+        let last = chars[chars.len() - 1];
+        if last.is_ascii_uppercase() {
+            promotion = Some(piece_index(piece_from_letter(last)?));
+            chars.pop();
+        }
+    }
+
+    let mut piece = PieceName::Pawn;
+    if !chars.is_empty() && chars[0].is_ascii_uppercase() {
+        piece = piece_from_letter(chars.remove(0))?;
+    }
+
+    let destination = match castle_side {
+        Some(_) => None,
+        None => {
+            if chars.len() < 2 {
+                return None;
+            }
+            let square: String = chars.split_off(chars.len() - 2).into_iter().collect();
+            Some(parse_square(&square)?)
+        },
+    };
+
+    // Whatever remains disambiguates the origin square
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars {
+        match c {
+            'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(c as usize - '1' as usize),
+            _ => return None,
+        }
+    }
+
+    let candidates: Vec<GameState> = legal_next_states(state).into_iter().filter(|next| {
+        let change = match describe_change(state, next) {
+            Some(change) => change,
+            None => return false,
+        };
+        let moved = match state.squares[change.from] {
+            Some(piece) => piece.name,
+            None => return false,
+        };
+
+        if let Some(direction) = castle_side {
+            let distance = change.to as i32 - change.from as i32;
+            return matches!(moved, PieceName::King) && distance == 2 * direction;
+        }
+
+        Some(change.to) == destination
+            && piece_index(moved) == piece_index(piece)
+            && change.promotion.map(piece_index) == promotion
+            && from_file.iter().all(|&file| change.from % 8 == file)
+            && from_rank.iter().all(|&rank| change.from / 8 == rank)
+    }).collect();
+
+    match candidates.len() {
+        1 => Some(candidates[0]),
+        _ => None,
+    }
+}
+
+#[test]
+fn parse_square_test() {
+    assert_eq!(parse_square("a1"), Some(0));
+    assert_eq!(parse_square("h1"), Some(7));
+    assert_eq!(parse_square("e4"), Some(28));
+    assert_eq!(parse_square("h8"), Some(63));
+    assert_eq!(parse_square("i1"), None);
+    assert_eq!(parse_square("a9"), None);
+    assert_eq!(square_name(28), "e4");
+}
+
+#[test]
+fn resolve_san_test() {
+    let state = GameState::new();
+
+    let next = resolve_san(&state, "e4").unwrap();
+    assert_eq!(coordinate_notation(&state, &next), Some("e2e4".to_string()));
+
+    let next = resolve_san(&state, "Nf3!").unwrap();
+    assert_eq!(coordinate_notation(&state, &next), Some("g1f3".to_string()));
+
+    let next = resolve_san(&state, "Ngf3").unwrap();
+    assert_eq!(coordinate_notation(&state, &next), Some("g1f3".to_string()));
+
+    assert!(resolve_san(&state, "e5").is_none());
+    assert!(resolve_san(&state, "O-O").is_none());
+    assert!(resolve_san(&state, "Qh5").is_none());
+}
+
+#[test]
+fn resolve_coordinate_move_test() {
+    let state = GameState::new();
+    let next = resolve_coordinate_move(&state, "b1c3").unwrap();
+    assert!(next.squares[1].is_none());
+    assert!(next.squares[18].is_some());
+    assert!(resolve_coordinate_move(&state, "e2e5").is_none());
+}
@@ -0,0 +1,6 @@
+
+mod writer;
+mod reader;
+
+pub use writer::*;
+pub use reader::*;
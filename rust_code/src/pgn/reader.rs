@@ -0,0 +1,335 @@
+
+use chess_engine::*;
+use crate::environment::{parse_fen, FenError};
+use crate::notation::resolve_san;
+use super::PgnGame;
+
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    UnterminatedTag,
+    UnterminatedComment,
+    UnbalancedVariation,
+    InvalidSetUp(FenError),
+    Unreadable(String),
+    IllegalMove { ply: usize, notation: String },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnError::UnterminatedTag => write!(f, "tag pair is missing a closing bracket"),
+            PgnError::UnterminatedComment => write!(f, "comment is missing a closing brace"),
+            PgnError::UnbalancedVariation => write!(f, "variation parentheses are unbalanced"),
+            PgnError::InvalidSetUp(error) => write!(f, "unable to set up position: {}", error),
+            PgnError::Unreadable(reason) => write!(f, "unable to read file: {}", reason),
+            PgnError::IllegalMove { ply, notation } => {
+                write!(f, "illegal move {} at ply {}", notation, ply)
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_notation(notation: &str) -> GameResult {
+        match notation {
+            "1-0" => GameResult::WhiteWins,
+            "0-1" => GameResult::BlackWins,
+            "1/2-1/2" => GameResult::Draw,
+            _ => GameResult::Unknown,
+        }
+    }
+
+    // The outcome as a value from white's perspective,
+    // matching the values stored in Experience.
+    pub fn value_for_white(&self) -> Option<f32> {
+        match self {
+            GameResult::WhiteWins => Some(1.0),
+            GameResult::BlackWins => Some(-1.0),
+            GameResult::Draw => Some(0.0),
+            GameResult::Unknown => None,
+        }
+    }
+}
+
+// A game whose movetext has been played out on the board.
+// The states begin with the initial position.
+pub struct ReplayedGame {
+    pub states: Vec<GameState>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    // Play the movetext out from the initial position,
+    // resolving each move against the legal actions.
+    pub fn replay(&self) -> Result<ReplayedGame, PgnError> {
+        let initial_state = match self.tag("FEN") {
+            Some(fen) => parse_fen(fen).map_err(PgnError::InvalidSetUp)?.state,
+            None => GameState::new(),
+        };
+
+        let mut states = vec![initial_state];
+        for (ply, notation) in self.moves.iter().enumerate() {
+            let state = states[states.len() - 1];
+            match resolve_san(&state, notation) {
+                Some(next_state) => states.push(next_state),
+                None => return Err(PgnError::IllegalMove {
+                    ply: ply + 1,
+                    notation: notation.clone(),
+                }),
+            }
+        }
+
+        Ok(ReplayedGame {
+            states,
+            result: GameResult::from_notation(&self.result),
+        })
+    }
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+// Parse every game in a PGN database. Comments, variations
+// and numeric annotation glyphs are skipped, leaving only
+// the tags, the main line, and the result.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = vec![];
+    let mut game = empty_game();
+    let mut started = false;
+    let mut variation_depth = 0;
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut index = 0;
+    let mut at_line_start = true;
+
+    while index < chars.len() {
+        let c = chars[index];
+
+        // Escaped lines begin with a percent sign
+        if at_line_start && c == '%' {
+            while index < chars.len() && chars[index] != '\n' {
+                index += 1;
+            }
+            continue;
+        }
+        at_line_start = c == '\n';
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        match c {
+            '[' if variation_depth == 0 => {
+                // A tag after the movetext starts a new game
+                if !game.moves.is_empty() {
+                    games.push(game);
+                    game = empty_game();
+                }
+                let end = find_tag_end(&chars, index).ok_or(PgnError::UnterminatedTag)?;
+                let (name, value) = parse_tag(&chars[index + 1..end]);
+                game.set_tag(&name, &value);
+                started = true;
+                index = end + 1;
+            },
+            // Bracketed annotations, like [%clk 0:01], can
+            // appear inside variations, which are skipped
+            '[' => {
+                index = find_tag_end(&chars, index).ok_or(PgnError::UnterminatedTag)? + 1;
+            },
+            '{' => {
+                let end = (index..chars.len()).find(|&i| chars[i] == '}')
+                    .ok_or(PgnError::UnterminatedComment)?;
+                index = end + 1;
+            },
+            ';' => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+            },
+            '(' => {
+                variation_depth += 1;
+                index += 1;
+            },
+            ')' => {
+                if variation_depth == 0 {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                variation_depth -= 1;
+                index += 1;
+            },
+            _ => {
+                let start = index;
+                while index < chars.len() && !is_delimiter(chars[index]) {
+                    index += 1;
+                }
+                // Anything unexpected is skipped, rather than read forever
+                if index == start {
+                    index += 1;
+                    continue;
+                }
+                let token: String = chars[start..index].iter().collect();
+
+                if variation_depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+
+                if RESULTS.contains(&token.as_str()) {
+                    game.set_result(&token);
+                    games.push(game);
+                    game = empty_game();
+                    started = false;
+                    continue;
+                }
+
+                // Move numbers look like "12." or "12...", and may
+                // be written without a space before the move.
+                let notation = match token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.') {
+                    true => token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.'),
+                    false => token.as_str(),
+                };
+                if !notation.is_empty() {
+                    game.moves.push(notation.to_string());
+                }
+                started = true;
+            },
+        }
+    }
+
+    if variation_depth > 0 {
+        return Err(PgnError::UnbalancedVariation);
+    }
+
+    if started {
+        games.push(game);
+    }
+    Ok(games)
+}
+
+pub fn read_pgn_file(filename: &str) -> Result<Vec<PgnGame>, PgnError> {
+    match std::fs::read_to_string(filename) {
+        Ok(text) => parse_pgn(&text),
+        Err(error) => Err(PgnError::Unreadable(error.to_string())),
+    }
+}
+
+fn empty_game() -> PgnGame {
+    PgnGame {
+        tags: vec![],
+        moves: vec![],
+        result: "*".to_string(),
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '{' || c == '(' || c == ')' || c == ';' || c == '['
+}
+
+// Find the closing bracket of a tag pair,
+// ignoring any brackets inside the quoted value.
+fn find_tag_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut index = start + 1;
+    while index < chars.len() {
+        match chars[index] {
+            '\\' if in_quotes => index += 1,
+            '"' => in_quotes = !in_quotes,
+            ']' if !in_quotes => return Some(index),
+            _ => (),
+        }
+        index += 1;
+    }
+    None
+}
+
+fn parse_tag(chars: &[char]) -> (String, String) {
+    let text: String = chars.iter().collect();
+    let text = text.trim();
+    let name: String = text.chars().take_while(|c| !c.is_whitespace()).collect();
+
+    let mut value = String::new();
+    let mut escaped = false;
+    let quoted = text[name.len()..].trim();
+    let quoted = quoted.strip_prefix('"').unwrap_or(quoted);
+    let quoted = quoted.strip_suffix('"').unwrap_or(quoted);
+    for c in quoted.chars() {
+        match (escaped, c) {
+            (false, '\\') => escaped = true,
+            _ => {
+                value.push(c);
+                escaped = false;
+            },
+        }
+    }
+
+    (name, value)
+}
+
+#[test]
+fn parse_pgn_test() {
+    let text = "[Event \"Casual \\\"Game\\\"\"]\n\
+        [Site \"?\"]\n\
+        [Result \"1-0\"]\n\
+        \n\
+        % this line is escaped\n\
+        1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5)) 2...Nc6 $1\n\
+        3. Bb5 ; the Spanish\n\
+        a6 4. O-O 1-0\n\
+        \n\
+        [Event \"Second\"]\n\
+        \n\
+        1. d4 d5 *\n";
+
+    let games = parse_pgn(text).unwrap();
+    assert_eq!(games.len(), 2);
+
+    assert_eq!(games[0].tag("Event"), Some("Casual \"Game\""));
+    assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]);
+    assert_eq!(games[0].result, "1-0");
+    assert_eq!(games[1].tag("Event"), Some("Second"));
+    assert_eq!(games[1].moves, vec!["d4", "d5"]);
+
+    let replayed = games[0].replay().unwrap();
+    assert_eq!(replayed.states.len(), 8);
+    assert_eq!(replayed.result, GameResult::WhiteWins);
+    assert_eq!(replayed.result.value_for_white(), Some(1.0));
+
+    // Annotations in brackets may appear inside variations
+    let games = parse_pgn("1. e4 (1. d4 [%clk 0:01] d5) e5 *").unwrap();
+    assert_eq!(games[0].moves, vec!["e4", "e5"]);
+}
+
+#[test]
+fn replay_from_fen_test() {
+    let text = "[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n\n1. O-O-O Kf7 2. Kb1 *";
+    let games = parse_pgn(text).unwrap();
+    let replayed = games[0].replay().unwrap();
+    assert_eq!(replayed.states.len(), 4);
+    assert_eq!(replayed.result, GameResult::Unknown);
+
+    let games = parse_pgn("[FEN \"8/8/8/8 w - - 0 1\"]\n\n*").unwrap();
+    assert_eq!(
+        games[0].replay().err(),
+        Some(PgnError::InvalidSetUp(FenError::WrongRankCount(4))),
+    );
+}
+
+#[test]
+fn parse_pgn_error_test() {
+    assert_eq!(parse_pgn("[Event \"?\"").err(), Some(PgnError::UnterminatedTag));
+    assert_eq!(parse_pgn("1. e4 { e5").err(), Some(PgnError::UnterminatedComment));
+    assert_eq!(parse_pgn("1. e4 (1. d4 *").err(), Some(PgnError::UnbalancedVariation));
+
+    let games = parse_pgn("1. e4 e4 *").unwrap();
+    assert_eq!(
+        games[0].replay().err(),
+        Some(PgnError::IllegalMove { ply: 2, notation: "e4".to_string() }),
+    );
+}
@@ -0,0 +1,235 @@
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chess_engine::*;
+use crate::agent::{ChessAgent, Policy};
+use crate::environment::*;
+
+// The tags every PGN game is required to carry,
+// in the order they must be exported.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = [
+    "Event",
+    "Site",
+    "Date",
+    "Round",
+    "White",
+    "Black",
+    "Result",
+];
+
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn new() -> PgnGame {
+        let mut game = PgnGame {
+            tags: vec![],
+            moves: vec![],
+            result: "*".to_string(),
+        };
+
+        for name in SEVEN_TAG_ROSTER.iter() {
+            game.set_tag(name, "?");
+        }
+        game.set_tag("Date", &todays_date());
+        game.set_tag("Result", "*");
+        game
+    }
+
+    // Record every ply played in an environment, along with
+    // the result if the game has finished.
+    pub fn from_environment(environment: &ChessEnvironment) -> PgnGame {
+        let mut game = PgnGame::new();
+
+        let initial_fen = environment.initial_fen();
+        if initial_fen != ChessEnvironment::new().initial_fen() {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &initial_fen);
+        }
+
+        game.moves = environment.history().into_iter()
+            .map(|ply| ply.action.unwrap_or_else(|| "--".to_string()))
+            .collect();
+
+        game.set_result(&result_notation(environment));
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.result = result.to_string();
+        self.set_tag("Result", result);
+    }
+
+    // Describe the settings an agent played with,
+    // so that games can be compared across runs.
+    pub fn record_agent_settings(&mut self, agent: &ChessAgent) {
+        self.set_tag("Foresight", &agent.foresight.to_string());
+        self.set_tag("Discount", &agent.discount.to_string());
+        self.set_tag("ExplorationPropensity", &agent.exploration_propensity.to_string());
+        self.set_tag("Policy", agent.policy.name());
+        if agent.policy == Policy::MonteCarloTreeSearch {
+            self.set_tag("Simulations", &agent.mcts.simulations.to_string());
+            self.set_tag("Temperature", &agent.mcts.temperature.to_string());
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut text = String::new();
+
+        // The seven tag roster comes first, in order,
+        // followed by any supplemental tags.
+        let roster = SEVEN_TAG_ROSTER.iter().filter_map(|name| {
+            self.tags.iter().find(|(tag, _)| tag == name)
+        });
+        let supplemental = self.tags.iter().filter(|(tag, _)| {
+            !SEVEN_TAG_ROSTER.contains(&tag.as_str())
+        });
+
+        for (name, value) in roster.chain(supplemental) {
+            text.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+        text.push('\n');
+
+        let mut tokens = vec![];
+        let black_moves_first = match self.tag("FEN") {
+            Some(fen) => fen.split_whitespace().nth(1) == Some("b"),
+            None => false,
+        };
+        let offset = if black_moves_first { 1 } else { 0 };
+        let first_move_number = match self.tag("FEN") {
+            Some(fen) => fen.split_whitespace().nth(5).and_then(|n| n.parse().ok()).unwrap_or(1),
+            None => 1,
+        };
+
+        for (index, notation) in self.moves.iter().enumerate() {
+            let ply = index + offset;
+            let move_number = first_move_number + ply / 2;
+            if ply % 2 == 0 {
+                tokens.push(format!("{}.", move_number));
+            } else if index == 0 {
+                tokens.push(format!("{}...", move_number));
+            }
+            tokens.push(notation.clone());
+        }
+        tokens.push(self.result.clone());
+
+        // Export format limits movetext lines to 80 characters
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() + 1 > 80 {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        text.push_str(&line);
+        text.push_str("\n\n");
+        text
+    }
+
+    // Append this game to a .pgn file, creating it if needed.
+    pub fn append_to_file(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?;
+
+        file.write_all(self.to_pgn().as_bytes())
+    }
+}
+
+impl Default for PgnGame {
+    fn default() -> PgnGame {
+        PgnGame::new()
+    }
+}
+
+pub fn result_notation(environment: &ChessEnvironment) -> String {
+    if !environment.is_terminated() {
+        return "*".to_string();
+    }
+
+    match environment.terminal_state(Color::White) {
+        TerminalState::Win => "1-0",
+        TerminalState::Loss => "0-1",
+        TerminalState::Draw(_) => "1/2-1/2",
+    }.to_string()
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+// The current UTC date, formatted as YYYY.MM.DD.
+fn todays_date() -> String {
+    let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => return "????.??.??".to_string(),
+    };
+
+    // Convert days since the epoch into a civil date
+    let days = seconds / 86_400 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[test]
+fn to_pgn_test() {
+    let mut game = PgnGame::new();
+    game.set_tag("Date", "2020.06.01");
+    game.set_tag("White", "ChessAgent");
+    game.set_tag("Black", "Random \"Mover\"");
+    game.set_tag("Foresight", "4");
+    game.moves = vec!["e4".to_string(), "e5".to_string(), "Nf3".to_string()];
+    game.set_result("1/2-1/2");
+
+    let expected = "[Event \"?\"]\n\
+        [Site \"?\"]\n\
+        [Date \"2020.06.01\"]\n\
+        [Round \"?\"]\n\
+        [White \"ChessAgent\"]\n\
+        [Black \"Random \\\"Mover\\\"\"]\n\
+        [Result \"1/2-1/2\"]\n\
+        [Foresight \"4\"]\n\
+        \n\
+        1. e4 e5 2. Nf3 1/2-1/2\n\n";
+
+    assert_eq!(game.to_pgn(), expected);
+}
+
+#[test]
+fn todays_date_test() {
+    let date = todays_date();
+    assert_eq!(date.len(), 10);
+    assert_eq!(&date[4..5], ".");
+    assert_eq!(&date[7..8], ".");
+}
@@ -0,0 +1,181 @@
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::agent::AgentConfig;
+
+// Where named profiles are kept unless told otherwise
+pub const PROFILE_DIRECTORY: &str = "./profiles";
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(String),
+    Serialize(String),
+    Deserialize(String),
+    InvalidName(String),
+    NotFound(String),
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProfileError::Io(message) => write!(f, "{}", message),
+            ProfileError::Serialize(message) => write!(f, "unable to write profile: {}", message),
+            ProfileError::Deserialize(message) => write!(f, "unable to read profile: {}", message),
+            ProfileError::InvalidName(name) => write!(f, "invalid profile name {:?}", name),
+            ProfileError::NotFound(name) => write!(f, "no profile named {}", name),
+        }
+    }
+}
+
+// Settings files may be written in either RON or JSON
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProfileFormat {
+    Ron,
+    Json,
+}
+
+impl ProfileFormat {
+    // JSON for files ending in .json, and RON for everything else
+    pub fn of(path: &Path) -> ProfileFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ProfileFormat::Json,
+            _ => ProfileFormat::Ron,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProfileFormat::Ron => "ron",
+            ProfileFormat::Json => "json",
+        }
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, ProfileError> {
+        let text = match self {
+            ProfileFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+                .map_err(|error| error.to_string()),
+            ProfileFormat::Json => serde_json::to_string_pretty(value)
+                .map_err(|error| error.to_string()),
+        };
+        text.map_err(ProfileError::Serialize)
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, text: &str) -> Result<T, ProfileError> {
+        let value = match self {
+            ProfileFormat::Ron => ron::de::from_str(text).map_err(|error| error.to_string()),
+            ProfileFormat::Json => serde_json::from_str(text).map_err(|error| error.to_string()),
+        };
+        value.map_err(ProfileError::Deserialize)
+    }
+}
+
+// Write anything serializable to a file, in the format
+// its extension calls for.
+pub fn save_settings<T: Serialize>(value: &T, path: &Path) -> Result<(), ProfileError> {
+    let text = ProfileFormat::of(path).serialize(value)?;
+    fs::write(path, text).map_err(|error| ProfileError::Io(format!("{}: {}", path.display(), error)))
+}
+
+pub fn load_settings<T: DeserializeOwned>(path: &Path) -> Result<T, ProfileError> {
+    let text = fs::read_to_string(path)
+        .map_err(|error| ProfileError::Io(format!("{}: {}", path.display(), error)))?;
+    ProfileFormat::of(path).deserialize(&text)
+        .map_err(|error| ProfileError::Deserialize(format!("{}: {}", path.display(), error)))
+}
+
+// A profile is an agent's settings saved under a name, as
+// <name>.ron or <name>.json in the profile directory, so the
+// same agent can be set up again exactly, by anyone.
+fn profile_path(directory: &str, name: &str, format: ProfileFormat) -> Result<PathBuf, ProfileError> {
+    let invalid = name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']);
+    if invalid {
+        return Err(ProfileError::InvalidName(name.to_string()));
+    }
+    Ok(Path::new(directory).join(format!("{}.{}", name, format.extension())))
+}
+
+pub fn save_profile(config: &AgentConfig, directory: &str, name: &str, format: ProfileFormat) -> Result<PathBuf, ProfileError> {
+    let path = profile_path(directory, name, format)?;
+    fs::create_dir_all(directory).map_err(|error| ProfileError::Io(format!("{}: {}", directory, error)))?;
+    save_settings(config, &path)?;
+    Ok(path)
+}
+
+// Load a named profile, whichever format it was saved in
+pub fn load_profile(directory: &str, name: &str) -> Result<AgentConfig, ProfileError> {
+    for format in [ProfileFormat::Ron, ProfileFormat::Json] {
+        let path = profile_path(directory, name, format)?;
+        if path.exists() {
+            return load_settings(&path);
+        }
+    }
+    Err(ProfileError::NotFound(name.to_string()))
+}
+
+// The names of every profile in a directory, in order
+pub fn profile_names(directory: &str) -> Result<Vec<String>, ProfileError> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(ProfileError::Io(format!("{}: {}", directory, error))),
+    };
+
+    let mut names = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_profile = matches!(path.extension().and_then(|extension| extension.to_str()), Some("ron") | Some("json"));
+        if let (true, Some(name)) = (is_profile, path.file_stem().and_then(|stem| stem.to_str())) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+#[test]
+fn profile_test() {
+    use chess_engine::Color;
+    use crate::agent::{Policy, Recollection};
+    use crate::training::{Opponent, TrainingOptions};
+
+    let options = TrainingOptions {
+        game_limit: 12,
+        opponent: Opponent::Material,
+        seed: Some(42),
+        starting_fen: Some("k7/8/8/8/8/8/8/K6R w - - 0 1".to_string()),
+        ..TrainingOptions::default()
+    };
+    let recollection = Recollection { times_encountered: 3, average_value: -0.125 };
+
+    for format in [ProfileFormat::Ron, ProfileFormat::Json] {
+        let text = format.serialize(&options).unwrap();
+        assert_eq!(format.deserialize::<TrainingOptions>(&text).unwrap(), options);
+
+        let text = format.serialize(&recollection).unwrap();
+        assert_eq!(format.deserialize::<Recollection>(&text).unwrap(), recollection);
+    }
+
+    let directory = std::env::temp_dir().join(format!("profile_test_{}", std::process::id()));
+    let directory = directory.to_str().unwrap();
+    let _ = fs::remove_dir_all(directory);
+
+    let config = AgentConfig {
+        playing_as: Color::Black,
+        foresight: 3,
+        policy: Policy::MonteCarloTreeSearch,
+        ..AgentConfig::default()
+    };
+    save_profile(&config, directory, "cautious", ProfileFormat::Ron).unwrap();
+    save_profile(&AgentConfig::default(), directory, "baseline", ProfileFormat::Json).unwrap();
+
+    assert_eq!(load_profile(directory, "cautious").unwrap(), config);
+    assert_eq!(load_profile(directory, "baseline").unwrap(), AgentConfig::default());
+    assert_eq!(profile_names(directory).unwrap(), vec!["baseline", "cautious"]);
+    assert!(matches!(load_profile(directory, "reckless"), Err(ProfileError::NotFound(_))));
+    assert!(matches!(load_profile(directory, "../cautious"), Err(ProfileError::InvalidName(_))));
+
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,98 @@
+
+use rand::{Error, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// A small random number generator whose whole state is a
+// single number, so it can be saved with a checkpoint and
+// picked back up exactly. Every random decision made by an
+// agent or environment is drawn from one of these, so two
+// runs given the same seed make the same decisions.
+//
+// This is SplitMix64, which is fast and statistically sound,
+// but not suitable for anything needing secrecy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng { state: seed }
+    }
+
+    // A generator seeded from the operating system, for
+    // when runs don't need to be repeatable.
+    pub fn unseeded() -> SeededRng {
+        SeededRng::new(rand::thread_rng().next_u64())
+    }
+
+    // One of many numbered generators drawn from the same seed,
+    // such as one for every game, so that each can be recreated
+    // without replaying the others. The streams are unrelated
+    // to each other, and to SeededRng::new(seed).
+    pub fn stream(seed: u64, index: u64) -> SeededRng {
+        let mut mixer = SeededRng::new(seed ^ index.wrapping_add(1).wrapping_mul(0xd1b5_4a32_d192_ed03));
+        SeededRng::new(mixer.next_u64())
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for SeededRng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: [u8; 8]) -> SeededRng {
+        SeededRng::new(u64::from_le_bytes(seed))
+    }
+
+    fn seed_from_u64(seed: u64) -> SeededRng {
+        SeededRng::new(seed)
+    }
+}
+
+#[test]
+fn seeded_rng_test() {
+    use rand::Rng;
+
+    let mut first = SeededRng::new(7);
+    let mut second = SeededRng::new(7);
+    let draws: Vec<u32> = (0..16).map(|_| first.gen_range(0, 100)).collect();
+    assert_eq!(draws, (0..16).map(|_| second.gen_range(0, 100)).collect::<Vec<u32>>());
+    let mut other = SeededRng::new(8);
+    assert_ne!(draws, (0..16).map(|_| other.gen_range(0, 100)).collect::<Vec<u32>>());
+    assert_ne!(SeededRng::stream(7, 0).next_u64(), SeededRng::stream(7, 1).next_u64());
+
+    // A generator restored from its saved state carries on
+    // exactly where the original left off.
+    let saved = ron::ser::to_string(&first).unwrap();
+    let mut restored: SeededRng = ron::de::from_str(&saved).unwrap();
+    assert_eq!(restored.next_u64(), first.next_u64());
+
+    let mut bytes = [0; 11];
+    restored.fill_bytes(&mut bytes);
+    assert!(bytes.iter().any(|byte| *byte != 0));
+}
@@ -0,0 +1,195 @@
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::agent::{AgentConfig, ChessAgent};
+use crate::random::SeededRng;
+use super::{TrainingOptions, TrainingStatistics};
+
+const CHECKPOINT_FILE: &str = "checkpoint.ron";
+const EXPERIENCE_FILE: &str = "experience.bin";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(String),
+    Serialize(String),
+    Deserialize(String),
+    BackupExists(String),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(message) => write!(f, "{}", message),
+            CheckpointError::Serialize(message) => write!(f, "unable to write checkpoint: {}", message),
+            CheckpointError::Deserialize(message) => write!(f, "unable to read checkpoint: {}", message),
+            CheckpointError::BackupExists(path) => {
+                write!(f, "{} already holds a replaced store, move it aside before restoring again", path)
+            },
+        }
+    }
+}
+
+fn io_error(path: &Path) -> impl Fn(std::io::Error) -> CheckpointError + '_ {
+    move |error| CheckpointError::Io(format!("{}: {}", path.display(), error))
+}
+
+// Everything needed to pick a training run back up: the agent's
+// settings and random state, the run's options, and how far it
+// has come. Each checkpoint is a directory holding this record,
+// in RON, beside a copy of the agent's experience store. The
+// opponent's state isn't recorded, so only runs whose options
+// are resumable can be picked back up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub agent: AgentConfig,
+    pub options: TrainingOptions,
+    pub statistics: TrainingStatistics,
+    pub agent_rng: SeededRng,
+}
+
+impl Checkpoint {
+    // Write a checkpoint into a new directory inside the given one,
+    // named after the number of games played. Unsaved experience is
+    // written to the store first, so the copy is complete.
+    pub fn save(&self, agent: &mut ChessAgent, directory: &str) -> Result<PathBuf, CheckpointError> {
+        agent.experience.long_term_memorize().map_err(io_error(Path::new(directory)))?;
+
+        let name = format!("game-{:08}", self.statistics.games_played);
+        let destination = Path::new(directory).join(&name);
+        let partial = Path::new(directory).join(format!("{}.partial", name));
+
+        // Build the checkpoint beside its destination, and only move it
+        // into place once it's whole, so an interrupted save can't leave
+        // a checkpoint that looks usable.
+        if partial.exists() {
+            fs::remove_dir_all(&partial).map_err(io_error(&partial))?;
+        }
+        fs::create_dir_all(&partial).map_err(io_error(&partial))?;
+
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| CheckpointError::Serialize(error.to_string()))?;
+        fs::write(partial.join(CHECKPOINT_FILE), text).map_err(io_error(&partial))?;
+
+        let store = agent.experience.store_path();
+        if store.exists() {
+            fs::copy(&store, partial.join(EXPERIENCE_FILE)).map_err(io_error(&store))?;
+        }
+
+        if destination.exists() {
+            fs::remove_dir_all(&destination).map_err(io_error(&destination))?;
+        }
+        fs::rename(&partial, &destination).map_err(io_error(&destination))?;
+        Ok(destination)
+    }
+
+    // Read the checkpoint in a directory written by save
+    pub fn load(directory: &str) -> Result<Checkpoint, CheckpointError> {
+        let path = Path::new(directory).join(CHECKPOINT_FILE);
+        let text = fs::read_to_string(&path).map_err(io_error(&path))?;
+        ron::de::from_str(&text).map_err(|error| CheckpointError::Deserialize(error.to_string()))
+    }
+
+    // Recreate the agent as it was at the checkpoint, putting the
+    // saved experience store back in place of the current one. The
+    // current store is moved aside rather than lost, in case the
+    // wrong checkpoint was resumed, and an earlier one that was
+    // moved aside is never overwritten.
+    pub fn restore_agent(&self, directory: &str) -> Result<ChessAgent, CheckpointError> {
+        let mut agent = ChessAgent::from_config(&self.agent);
+        agent.positions_evaluated = self.statistics.positions_evaluated;
+        agent.rng = self.agent_rng.clone();
+
+        let saved_store = Path::new(directory).join(EXPERIENCE_FILE);
+        let store = agent.experience.store_path();
+        if store.exists() {
+            let backup = replaced_store_path(&store);
+            if backup.exists() {
+                return Err(CheckpointError::BackupExists(backup.display().to_string()));
+            }
+            fs::rename(&store, &backup).map_err(io_error(&backup))?;
+        }
+        if saved_store.exists() {
+            fs::copy(&saved_store, &store).map_err(io_error(&store))?;
+        }
+
+        agent.experience.long_term_recall().map_err(io_error(&store))?;
+        Ok(agent)
+    }
+
+    // Recreate the agent as it was at the checkpoint, reading the
+    // saved experience store where it is. The agent is meant only
+    // for play, so the store is opened read-only.
+    pub fn frozen_agent(&self, directory: &str) -> Result<ChessAgent, CheckpointError> {
+        let mut agent = ChessAgent::from_config(&self.agent);
+        agent.experience.long_term_memory_file = directory.to_string();
+        agent.experience.long_term_recall_read_only().map_err(io_error(Path::new(directory)))?;
+        Ok(agent)
+    }
+}
+
+// Where restore_agent moves the store it replaces
+pub fn replaced_store_path(store: &Path) -> PathBuf {
+    let mut path = store.to_path_buf().into_os_string();
+    path.push(".replaced");
+    PathBuf::from(path)
+}
+
+#[test]
+fn checkpoint_test() {
+    use chess_engine::*;
+    use crate::environment::ChessEnvironment;
+
+    let directory = std::env::temp_dir().join(format!("checkpoint_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let directory = directory.to_str().unwrap().to_string();
+
+    let mut agent = ChessAgent::new();
+    agent.foresight = 3;
+    agent.experience.long_term_memory_file = format!("{}/experience", directory);
+    let environment = ChessEnvironment::new();
+    agent.experience.memorize(&environment, 0.5);
+
+    let checkpoint = Checkpoint {
+        agent: agent.config(),
+        options: TrainingOptions {
+            game_limit: 10,
+            turn_limit: 20,
+            study_pgn_file: Some("games.pgn".to_string()),
+            checkpoint_directory: Some(directory.clone()),
+            checkpoint_interval: 2,
+            ..TrainingOptions::default()
+        },
+        statistics: TrainingStatistics {
+            games_played: 4,
+            wins: 1,
+            ..TrainingStatistics::default()
+        },
+        agent_rng: SeededRng::new(11),
+    };
+    let saved = checkpoint.save(&mut agent, &directory).unwrap();
+    assert!(saved.ends_with("game-00000004"));
+
+    // Experience gathered after the checkpoint is rolled back
+    agent.experience.memorize(&environment.branch(legal_next_states(&environment.state)[0]), 1.0);
+    agent.experience.long_term_memorize().unwrap();
+
+    let loaded = Checkpoint::load(saved.to_str().unwrap()).unwrap();
+    assert_eq!(loaded, checkpoint);
+
+    let restored = loaded.restore_agent(saved.to_str().unwrap()).unwrap();
+    assert_eq!(restored.config(), agent.config());
+    assert_eq!(restored.rng, SeededRng::new(11));
+    assert_eq!(restored.experience.stored_len(), 1);
+    assert_eq!(restored.experience.value_of(&environment.state), 0.5);
+
+    // The store it replaced is kept beside it
+    let replaced = crate::agent::ExperienceStore::open(&replaced_store_path(&restored.experience.store_path())).unwrap();
+    assert_eq!(replaced.len(), 2);
+
+    // and isn't overwritten by restoring again
+    assert!(matches!(loaded.restore_agent(saved.to_str().unwrap()), Err(CheckpointError::BackupExists(_))));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
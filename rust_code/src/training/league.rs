@@ -0,0 +1,302 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::{ChessAgent, MoveOrdering};
+use crate::environment::*;
+use super::{Checkpoint, CheckpointError, TrainingOptions, TrainingStatistics};
+
+const LEAGUE_FILE: &str = "league.ron";
+
+// How often each snapshot in the league is chosen as an opponent
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SnapshotWeighting {
+    // Every snapshot equally often
+    Uniform,
+
+    // Each snapshot is chosen this many times as
+    // often as the snapshot taken after it
+    Recent(f32),
+
+    // Snapshots that have scored well against the
+    // agent are chosen more often
+    Prioritized,
+}
+
+impl SnapshotWeighting {
+    pub fn from_name(name: &str) -> Option<SnapshotWeighting> {
+        match name.to_lowercase().as_str() {
+            "uniform" => Some(SnapshotWeighting::Uniform),
+            "recent" => Some(SnapshotWeighting::Recent(0.8)),
+            "prioritized" => Some(SnapshotWeighting::Prioritized),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeagueOptions {
+    // Where snapshots are kept, one checkpoint directory each
+    pub directory: String,
+    // Games between snapshots
+    pub snapshot_interval: i32,
+    // The oldest snapshots are dropped beyond this many,
+    // or never when it's 0
+    pub max_snapshots: usize,
+    pub weighting: SnapshotWeighting,
+}
+
+impl Default for LeagueOptions {
+    fn default() -> LeagueOptions {
+        LeagueOptions {
+            directory: "./league".to_string(),
+            snapshot_interval: 50,
+            max_snapshots: 10,
+            weighting: SnapshotWeighting::Uniform,
+        }
+    }
+}
+
+// A snapshot of the agent, and how the agent
+// has fared against it since it was taken
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub games_played: i32,
+    pub wins: i32,
+    pub losses: i32,
+    pub draws: i32,
+}
+
+impl Snapshot {
+    // The snapshot's score against the agent, from 0 to 1, starting
+    // from an even score so that new snapshots aren't neglected.
+    pub fn score(&self) -> f32 {
+        let games = (self.wins + self.losses + self.draws) as f32;
+        (self.losses as f32 + self.draws as f32 / 2.0 + 1.0) / (games + 2.0)
+    }
+}
+
+// A pool of frozen snapshots of the agent to train against,
+// so it doesn't only learn to beat its current self. Each
+// snapshot is a checkpoint, and the list of snapshots is
+// kept beside them in RON.
+pub struct League {
+    pub options: LeagueOptions,
+    snapshots: Vec<Snapshot>,
+    // Snapshot agents already loaded, by snapshot name
+    opponents: HashMap<String, ChessAgent>,
+}
+
+impl League {
+    // Open the league in the configured directory,
+    // creating an empty one if there isn't one yet.
+    pub fn open(options: &LeagueOptions) -> Result<League, CheckpointError> {
+        let path = Path::new(&options.directory).join(LEAGUE_FILE);
+        let snapshots = match path.exists() {
+            true => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|error| CheckpointError::Io(format!("{}: {}", path.display(), error)))?;
+                ron::de::from_str(&text).map_err(|error| CheckpointError::Deserialize(error.to_string()))?
+            },
+            false => vec![],
+        };
+
+        Ok(League {
+            options: options.clone(),
+            snapshots,
+            opponents: HashMap::new(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    // Oldest first
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    // Freeze a copy of the agent as it is now, dropping
+    // the oldest snapshots if there are too many.
+    pub fn add_snapshot(
+        &mut self,
+        agent: &mut ChessAgent,
+        options: &TrainingOptions,
+        statistics: &TrainingStatistics,
+    ) -> Result<PathBuf, CheckpointError> {
+        let checkpoint = Checkpoint {
+            agent: agent.config(),
+            options: options.clone(),
+            statistics: statistics.clone(),
+            agent_rng: agent.rng.clone(),
+        };
+        let path = checkpoint.save(agent, &self.options.directory)?;
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+        self.snapshots.retain(|snapshot| snapshot.name != name);
+        self.opponents.remove(&name);
+        self.snapshots.push(Snapshot {
+            name,
+            games_played: statistics.games_played,
+            ..Snapshot::default()
+        });
+
+        let max_snapshots = self.options.max_snapshots;
+        while max_snapshots > 0 && self.snapshots.len() > max_snapshots {
+            let oldest = self.snapshots.remove(0);
+            self.opponents.remove(&oldest.name);
+            let directory = Path::new(&self.options.directory).join(&oldest.name);
+            fs::remove_dir_all(&directory)
+                .map_err(|error| CheckpointError::Io(format!("{}: {}", directory.display(), error)))?;
+        }
+
+        self.save()?;
+        Ok(path)
+    }
+
+    // The chance of choosing each snapshot, in the same order
+    pub fn weights(&self) -> Vec<f32> {
+        let count = self.snapshots.len();
+        let weights: Vec<f32> = match self.options.weighting {
+            SnapshotWeighting::Uniform => vec![1.0; count],
+            SnapshotWeighting::Recent(decay) => {
+                (0..count).map(|index| decay.powi((count - 1 - index) as i32)).collect()
+            },
+            SnapshotWeighting::Prioritized => self.snapshots.iter().map(Snapshot::score).collect(),
+        };
+
+        let total: f32 = weights.iter().sum();
+        weights.into_iter().map(|weight| weight / total).collect()
+    }
+
+    // The index of a snapshot to play against
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        if self.snapshots.is_empty() {
+            return None;
+        }
+
+        let mut remaining = rng.gen::<f32>();
+        for (index, weight) in self.weights().into_iter().enumerate() {
+            remaining -= weight;
+            if remaining <= 0.0 {
+                return Some(index);
+            }
+        }
+        Some(self.snapshots.len() - 1)
+    }
+
+    // The agent a snapshot was taken of. Each snapshot is only read
+    // once, and whatever it learned while playing earlier games is
+    // forgotten, so every game is against the snapshot as it was.
+    pub fn load_opponent(&mut self, index: usize) -> Result<&mut ChessAgent, CheckpointError> {
+        let snapshot = &self.snapshots[index];
+        let name = &snapshot.name;
+        let opponent = match self.opponents.entry(name.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let directory = Path::new(&self.options.directory).join(name);
+                let directory = directory.to_string_lossy();
+                entry.insert(Checkpoint::load(&directory)?.frozen_agent(&directory)?)
+            },
+        };
+        // Snapshots explore as the agent did when they were taken
+        opponent.games_played = snapshot.games_played as u32;
+        opponent.experience.forget_short_term();
+        opponent.transposition_table.clear();
+        opponent.ordering = MoveOrdering::default();
+        Ok(opponent)
+    }
+
+    // Record a finished game between the agent and a snapshot
+    pub fn record_result(
+        &mut self,
+        index: usize,
+        environment: &ChessEnvironment,
+        agent_color: Color,
+    ) -> Result<(), CheckpointError> {
+        if !environment.is_terminated() {
+            return Ok(());
+        }
+
+        let snapshot = &mut self.snapshots[index];
+        match environment.terminal_state(agent_color) {
+            TerminalState::Win => snapshot.wins += 1,
+            TerminalState::Loss => snapshot.losses += 1,
+            TerminalState::Draw(_) => snapshot.draws += 1,
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), CheckpointError> {
+        let path = Path::new(&self.options.directory).join(LEAGUE_FILE);
+        let text = ron::ser::to_string_pretty(&self.snapshots, ron::ser::PrettyConfig::default())
+            .map_err(|error| CheckpointError::Serialize(error.to_string()))?;
+        fs::write(&path, text).map_err(|error| CheckpointError::Io(format!("{}: {}", path.display(), error)))
+    }
+}
+
+#[test]
+fn league_test() {
+    let directory = std::env::temp_dir().join(format!("league_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let experience = directory.join("experience");
+    let directory = directory.to_str().unwrap().to_string();
+
+    let mut agent = ChessAgent::new();
+    agent.experience.long_term_memory_file = experience.to_str().unwrap().to_string();
+    let options = LeagueOptions {
+        directory: format!("{}/league", directory),
+        max_snapshots: 2,
+        weighting: SnapshotWeighting::Prioritized,
+        ..LeagueOptions::default()
+    };
+    fs::create_dir_all(&options.directory).unwrap();
+
+    let mut league = League::open(&options).unwrap();
+    let mut statistics = TrainingStatistics::default();
+    let environment = ChessEnvironment::new();
+    for games_played in 0..3 {
+        statistics.games_played = games_played;
+        agent.experience.memorize(&environment, games_played as f32 / 4.0);
+        league.add_snapshot(&mut agent, &TrainingOptions::default(), &statistics).unwrap();
+    }
+
+    // The oldest snapshot was dropped
+    assert_eq!(league.len(), 2);
+    assert_eq!(league.snapshots()[0].games_played, 1);
+    assert!(!Path::new(&options.directory).join("game-00000000").exists());
+
+    // Snapshots that beat the agent are chosen more often
+    let (mut lost, mate) = rook_mate_in_one();
+    lost.apply_change(mate);
+    league.record_result(1, &lost, Color::Black).unwrap();
+    let weights = league.weights();
+    assert!(weights[1] > weights[0]);
+    assert!((weights.iter().sum::<f32>() - 1.0).abs() < 0.001);
+
+    // Snapshots keep the experience they were taken with,
+    // here the average of 0.0 and 0.25
+    let mut reopened = League::open(&options).unwrap();
+    assert_eq!(reopened.snapshots(), league.snapshots());
+    let opponent = reopened.load_opponent(0).unwrap();
+    assert_eq!(opponent.experience.value_of(&environment.state), 0.125);
+    assert_eq!(opponent.games_played, 1);
+
+    // and never learn anything more
+    opponent.experience.memorize(&environment, 1.0);
+    assert!(opponent.experience.long_term_memorize().is_err());
+    let opponent = reopened.load_opponent(0).unwrap();
+    assert_eq!(opponent.experience.value_of(&environment.state), 0.125);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,318 @@
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use chess_engine::*;
+use crate::agent::AgentConfig;
+use crate::cli::CliError;
+use crate::environment::*;
+use crate::profile::{load_settings, ProfileError};
+
+mod checkpoint;
+pub use checkpoint::*;
+
+mod league;
+pub use league::*;
+
+// Who the agent plays against in training
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Opponent {
+    // Any legal move, chosen at random
+    Random,
+
+    // The move that leaves the best material balance,
+    // with ties broken at random
+    Material,
+
+    // The agent itself, or a second agent with its own
+    // experience, with every finished game studied by both
+    SelfPlay,
+
+    // Frozen snapshots of the agent from earlier in training
+    League,
+}
+
+impl Opponent {
+    pub fn from_name(name: &str) -> Option<Opponent> {
+        match name.to_lowercase().as_str() {
+            "random" => Some(Opponent::Random),
+            "material" => Some(Opponent::Material),
+            "selfplay" | "self-play" | "self_play" => Some(Opponent::SelfPlay),
+            "league" => Some(Opponent::League),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Opponent::Random => "Random",
+            Opponent::Material => "Material",
+            Opponent::SelfPlay => "SelfPlay",
+            Opponent::League => "League",
+        }
+    }
+
+    // Agents choose for themselves with ChessAgent::react,
+    // so there's no choice here when playing against one.
+    pub fn choose<R: Rng>(&self, environment: &ChessEnvironment, rng: &mut R) -> Option<GameState> {
+        match self {
+            Opponent::SelfPlay | Opponent::League => None,
+            Opponent::Random => environment.random_decision_using(rng),
+            Opponent::Material => {
+                if environment.is_terminated() {
+                    return None;
+                }
+
+                let mover = environment.state.to_move;
+                let decisions = environment.available_decisions();
+                let balance = |state: &GameState| material_balance(state, mover);
+                let best_balance = decisions.iter().map(balance).fold(f32::NEG_INFINITY, f32::max);
+                let best: Vec<GameState> = decisions.into_iter()
+                    .filter(|decision| balance(decision) == best_balance)
+                    .collect();
+                Some(best[rng.gen_range(0, best.len())])
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingOptions {
+    pub game_limit: i32,
+    // Moves by each side before a game is abandoned
+    pub turn_limit: i32,
+    pub pgn_file: Option<String>,
+    pub study_pgn_file: Option<String>,
+    pub starting_fen: Option<String>,
+    pub opponent: Opponent,
+    // In self-play, the experience of a second agent to play
+    // against. Without one the agent plays both sides.
+    pub opponent_experience_file: Option<String>,
+    // The snapshots played against when the opponent is League
+    pub league: LeagueOptions,
+    // Seeds the opponent's choices, so runs can be repeated
+    pub seed: Option<u64>,
+    pub checkpoint_directory: Option<String>,
+    // Games between checkpoints
+    pub checkpoint_interval: i32,
+}
+
+impl TrainingOptions {
+    // Whether a checkpoint holds everything the run depends on. A
+    // self-play rival and the league keep experience and random
+    // state of their own that checkpoints don't record, so runs
+    // against them can't be resumed exactly.
+    pub fn resumable(&self) -> bool {
+        match self.opponent {
+            Opponent::Random | Opponent::Material => true,
+            Opponent::SelfPlay => self.opponent_experience_file.is_none(),
+            Opponent::League => false,
+        }
+    }
+}
+
+impl Default for TrainingOptions {
+    fn default() -> TrainingOptions {
+        TrainingOptions {
+            game_limit: 100,
+            turn_limit: 100,
+            pgn_file: None,
+            study_pgn_file: None,
+            starting_fen: None,
+            opponent: Opponent::Random,
+            opponent_experience_file: None,
+            league: LeagueOptions::default(),
+            seed: None,
+            checkpoint_directory: None,
+            checkpoint_interval: 100,
+        }
+    }
+}
+
+// A complete description of a training run, as read from
+// a RON or JSON file. Any field left out takes its default value.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingConfig {
+    pub options: TrainingOptions,
+    pub agent: AgentConfig,
+}
+
+impl TrainingConfig {
+    pub fn load(filename: &str) -> Result<TrainingConfig, CliError> {
+        load_settings(std::path::Path::new(filename)).map_err(config_error)
+    }
+
+    // Check for settings that would stop the run from
+    // working, before any time is spent on it.
+    pub fn validate(&self) -> Result<(), CliError> {
+        let invalid = |message: String| Err(CliError::InvalidConfig(message));
+        let options = &self.options;
+        let agent = &self.agent;
+
+        if options.game_limit < 0 {
+            return invalid(format!("game_limit must not be negative, found {}", options.game_limit));
+        }
+        if options.turn_limit < 1 {
+            return invalid(format!("turn_limit must be at least 1, found {}", options.turn_limit));
+        }
+        if options.checkpoint_directory.is_some() && options.checkpoint_interval < 1 {
+            return invalid(format!("checkpoint_interval must be at least 1, found {}", options.checkpoint_interval));
+        }
+        if let Some(path) = &options.opponent_experience_file {
+            if options.opponent != Opponent::SelfPlay {
+                return invalid("opponent_experience_file is only used in self-play".to_string());
+            }
+            if *path == agent.experience_file {
+                return invalid("opponent_experience_file must differ from experience_file".to_string());
+            }
+        }
+        if options.opponent == Opponent::League {
+            let league = &options.league;
+            if league.directory.is_empty() {
+                return invalid("league directory must not be empty".to_string());
+            }
+            if league.snapshot_interval < 1 {
+                return invalid(format!("league snapshot_interval must be at least 1, found {}", league.snapshot_interval));
+            }
+            if let SnapshotWeighting::Recent(decay) = league.weighting {
+                if !(decay > 0.0 && decay <= 1.0) {
+                    return invalid(format!("league Recent weighting must be in (0, 1], found {}", decay));
+                }
+            }
+        }
+        if let Some(fen) = &options.starting_fen {
+            if let Err(error) = parse_fen(fen) {
+                return invalid(format!("starting_fen {}: {}", fen, error));
+            }
+        }
+
+        if agent.foresight < 1 {
+            return invalid(format!("foresight must be at least 1, found {}", agent.foresight));
+        }
+        if !(agent.discount > 0.0 && agent.discount <= 1.0) {
+            return invalid(format!("discount must be in (0, 1], found {}", agent.discount));
+        }
+        if !(0.0..=1.0).contains(&agent.exploration_propensity) {
+            return invalid(format!("exploration_propensity must be in [0, 1], found {}", agent.exploration_propensity));
+        }
+        if let Err(message) = agent.exploration.validate() {
+            return invalid(format!("exploration: {}", message));
+        }
+        if agent.mcts.simulations < 1 {
+            return invalid("mcts simulations must be at least 1".to_string());
+        }
+        if agent.mcts.temperature.is_nan() || agent.mcts.temperature < 0.0 {
+            return invalid(format!("mcts temperature must not be negative, found {}", agent.mcts.temperature));
+        }
+        if agent.quiescence.max_depth < 0 {
+            return invalid(format!("quiescence max_depth must not be negative, found {}", agent.quiescence.max_depth));
+        }
+        if agent.experience_file.is_empty() {
+            return invalid("experience_file must not be empty".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// A settings file that can't be read is a different problem from
+// one that was read but doesn't describe valid settings.
+pub fn config_error(error: ProfileError) -> CliError {
+    match error {
+        ProfileError::Deserialize(message) => CliError::InvalidConfig(message),
+        error => CliError::UnreadableConfig(error.to_string()),
+    }
+}
+
+// Running totals for a training run, from the agent's perspective
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrainingStatistics {
+    pub games_played: i32,
+    pub wins: i32,
+    pub losses: i32,
+    pub draws: i32,
+    // Games cut short by the turn limit
+    pub unfinished: i32,
+    pub plies_played: u64,
+    pub positions_evaluated: u64,
+}
+
+impl TrainingStatistics {
+    pub fn record_game(&mut self, environment: &ChessEnvironment, agent_color: Color) {
+        self.games_played += 1;
+        self.plies_played += environment.plies_played() as u64;
+
+        if !environment.is_terminated() {
+            self.unfinished += 1;
+            return;
+        }
+
+        match environment.terminal_state(agent_color) {
+            TerminalState::Win => self.wins += 1,
+            TerminalState::Loss => self.losses += 1,
+            TerminalState::Draw(_) => self.draws += 1,
+        }
+    }
+}
+
+#[test]
+fn training_config_test() {
+    let config: TrainingConfig = ron::de::from_str("(
+        options: (game_limit: 5, opponent: Material, seed: Some(7)),
+        agent: (foresight: 2, policy: AlphaBeta),
+    )").unwrap();
+    assert_eq!(config.options.game_limit, 5);
+    assert_eq!(config.options.turn_limit, TrainingOptions::default().turn_limit);
+    assert_eq!(config.options.opponent, Opponent::Material);
+    assert_eq!(config.agent.foresight, 2);
+    assert_eq!(config.agent.discount, AgentConfig::default().discount);
+    assert_eq!(config.validate(), Ok(()));
+    assert!(config.options.resumable());
+
+    // A self-play rival's state isn't checkpointed
+    let mut rival = config.options.clone();
+    rival.opponent = Opponent::SelfPlay;
+    assert!(rival.resumable());
+    rival.opponent_experience_file = Some("rival".to_string());
+    assert!(!rival.resumable());
+
+    let mut invalid = config.clone();
+    invalid.agent.discount = 1.5;
+    assert!(matches!(invalid.validate(), Err(CliError::InvalidConfig(_))));
+
+    let unreadable = TrainingConfig::load("no_such_training_config.ron");
+    assert!(matches!(unreadable, Err(CliError::UnreadableConfig(_))));
+    let path = std::env::temp_dir().join(format!("training_config_test_{}.ron", std::process::id()));
+    std::fs::write(&path, "(options: (game_limit: \"five\"))").unwrap();
+    let misspelled = TrainingConfig::load(path.to_str().unwrap());
+    assert!(matches!(misspelled, Err(CliError::InvalidConfig(_))));
+    std::fs::remove_file(&path).unwrap();
+
+    let mut invalid = config;
+    invalid.options.starting_fen = Some("8/8/8/8 w - - 0 1".to_string());
+    assert!(matches!(invalid.validate(), Err(CliError::InvalidConfig(_))));
+}
+
+#[test]
+fn self_play_test() {
+    let mut agent = crate::agent::ChessAgent::new();
+    agent.policy = crate::agent::Policy::AlphaBeta;
+    agent.foresight = 2;
+
+    // White mates, playing against itself
+    let (mut environment, _) = rook_mate_in_one();
+    agent.playing_as = Color::Black;
+    let decision = agent.react_as_mover(&environment);
+    environment.apply_change(decision);
+    assert!(environment.is_terminated());
+    assert!(agent.playing_as == Color::Black);
+
+    let before = agent.experience.value_of(&environment.initial_state());
+    assert!(agent.study_environment(&environment));
+    assert!(agent.experience.value_of(&environment.initial_state()) > before);
+    assert!(!agent.study_environment(&ChessEnvironment::new()));
+
+    assert_eq!(Opponent::from_name("self-play"), Some(Opponent::SelfPlay));
+    assert!(Opponent::SelfPlay.choose(&environment, &mut crate::random::SeededRng::new(0)).is_none());
+}
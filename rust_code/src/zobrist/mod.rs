@@ -0,0 +1,308 @@
+
+use std::collections::HashMap;
+use chess_engine::*;
+use crate::notation::{describe_change, piece_index};
+
+// Zobrist keys give every feature of a position its own random
+// 64 bit number, and describe a position by XORing together the
+// numbers of the features it has. Moving a piece only toggles
+// the features that changed, so keys can be updated rather
+// than recomputed.
+
+// The keys are generated from a fixed seed, so positions have
+// the same key in every run and in every stored file.
+const SEED: u64 = 0x5eed_c4e5_5b0a_4d21;
+
+const fn splitmix64(index: u64) -> u64 {
+    let mut z = SEED.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0; 64]; 12];
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            keys[piece][square] = splitmix64((piece * 64 + square) as u64);
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+const fn feature_keys<const N: usize>(offset: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut index = 0;
+    while index < N {
+        keys[index] = splitmix64(offset + index as u64);
+        index += 1;
+    }
+    keys
+}
+
+// Indexed by color (white first), then piece_index, then square
+const PIECE_KEYS: [[u64; 64]; 12] = piece_keys();
+const BLACK_TO_MOVE_KEY: u64 = splitmix64(768);
+
+// White kingside, white queenside, black kingside, black queenside
+const CASTLING_KEYS: [u64; 4] = feature_keys(769);
+const EN_PASSANT_FILE_KEYS: [u64; 8] = feature_keys(773);
+
+pub fn piece_key(piece: Piece, square: usize) -> u64 {
+    let color = match piece.color {
+        Color::White => 0,
+        Color::Black => 6,
+    };
+    PIECE_KEYS[color + piece_index(piece.name)][square]
+}
+
+fn square_key(square: Option<Piece>, index: usize) -> u64 {
+    match square {
+        Some(piece) => piece_key(piece, index),
+        None => 0,
+    }
+}
+
+fn side_key(color: Color) -> u64 {
+    match color {
+        Color::White => 0,
+        Color::Black => BLACK_TO_MOVE_KEY,
+    }
+}
+
+fn castling_key(state: &GameState) -> u64 {
+    let rights = [
+        state.white_can_castle_kingside,
+        state.white_can_castle_queenside,
+        state.black_can_castle_kingside,
+        state.black_can_castle_queenside,
+    ];
+    rights.iter()
+        .zip(CASTLING_KEYS.iter())
+        .filter(|(right, _)| **right)
+        .fold(0, |key, (_, right_key)| key ^ right_key)
+}
+
+fn en_passant_key(state: &GameState) -> u64 {
+    match state.en_passant_square {
+        Some(square) => EN_PASSANT_FILE_KEYS[square % 8],
+        None => 0,
+    }
+}
+
+// The key of a position, covering the same features as the first
+// four fields of its FEN record: placement, side to move, castling
+// rights and the en passant file.
+pub fn zobrist_key(state: &GameState) -> u64 {
+    let mut key = side_key(state.to_move) ^ castling_key(state) ^ en_passant_key(state);
+    for (index, square) in state.squares.iter().enumerate() {
+        key ^= square_key(*square, index);
+    }
+    key
+}
+
+// Add a piece to, or remove it from, the position a key describes
+pub fn toggle_piece(key: u64, piece: Piece, square: usize) -> u64 {
+    key ^ piece_key(piece, square)
+}
+
+pub fn toggle_side_to_move(key: u64) -> u64 {
+    key ^ BLACK_TO_MOVE_KEY
+}
+
+// Derive the key of a position from the key of the position
+// before it, toggling only the features that differ. States
+// don't record the move between them, so it's recovered with
+// describe_change, and only the squares it touched are toggled.
+// States that aren't a single move apart get a fresh key.
+pub fn update_key(key: u64, before: &GameState, after: &GameState) -> u64 {
+    let change = match describe_change(before, after) {
+        Some(change) => change,
+        None => return zobrist_key(after),
+    };
+    let (moved, landed) = match (before.squares[change.from], after.squares[change.to]) {
+        (Some(moved), Some(landed)) => (moved, landed),
+        _ => return zobrist_key(after),
+    };
+
+    let mut key = key
+        ^ castling_key(before) ^ castling_key(after)
+        ^ en_passant_key(before) ^ en_passant_key(after);
+    if before.to_move != after.to_move {
+        key = toggle_side_to_move(key);
+    }
+
+    // The moving piece leaves its square and arrives at the other,
+    // as a different piece if it was promoted
+    key = toggle_piece(key, moved, change.from);
+    key ^= square_key(before.squares[change.to], change.to);
+    key = toggle_piece(key, landed, change.to);
+
+    let from_file = change.from % 8;
+    let to_file = change.to % 8;
+    let rank_start = change.from - from_file;
+    match moved.name {
+        // Castling is the only king move that crosses two files,
+        // and brings the corner rook to the square it crossed
+        PieceName::King if from_file.abs_diff(to_file) == 2 => {
+            let rook_from = match to_file > from_file {
+                true => rank_start + 7,
+                false => rank_start,
+            };
+            let rook_to = (change.from + change.to) / 2;
+            if let Some(rook) = before.squares[rook_from] {
+                key = toggle_piece(key, rook, rook_from);
+                key = toggle_piece(key, rook, rook_to);
+            }
+        },
+
+        // A pawn moving diagonally onto an empty square takes
+        // en passant, removing the pawn beside where it started
+        PieceName::Pawn if from_file != to_file && before.squares[change.to].is_none() => {
+            let victim = rank_start + to_file;
+            key ^= square_key(before.squares[victim], victim);
+        },
+        _ => (),
+    }
+    key
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CollisionStatistics {
+    // Distinct positions seen
+    pub positions: usize,
+    // Distinct keys those positions were given
+    pub keys: usize,
+    // Positions that were given a key another position already had
+    pub collisions: usize,
+}
+
+impl CollisionStatistics {
+    // The number of collisions expected between this many
+    // positions if keys were perfectly random, n² / 2⁶⁵.
+    pub fn expected_collisions(&self) -> f64 {
+        let positions = self.positions as f64;
+        positions * positions / 2f64.powi(65)
+    }
+}
+
+// Records the full position behind every key it sees, to detect
+// distinct positions sharing a key. This costs far more memory
+// than the keys save, so it's intended for diagnosis only.
+#[derive(Default)]
+pub struct CollisionDetector {
+    positions: HashMap<u64, Vec<[u64; 2]>>,
+    statistics: CollisionStatistics,
+}
+
+impl CollisionDetector {
+    pub fn new() -> CollisionDetector {
+        CollisionDetector::default()
+    }
+
+    // Record a position under its key, returning true if
+    // a different position was already recorded there.
+    pub fn record(&mut self, key: u64, state: &GameState) -> bool {
+        // A second, independently seeded key stands in
+        // for the full position.
+        let fingerprint = [key, independent_key(state)];
+        let positions = self.positions.entry(key).or_default();
+        if positions.contains(&fingerprint) {
+            return false;
+        }
+
+        let collided = !positions.is_empty();
+        positions.push(fingerprint);
+
+        self.statistics.positions += 1;
+        if collided {
+            self.statistics.collisions += 1;
+        } else {
+            self.statistics.keys += 1;
+        }
+        collided
+    }
+
+    pub fn statistics(&self) -> CollisionStatistics {
+        self.statistics
+    }
+}
+
+// A hash of the full position that shares nothing with the
+// zobrist keys, so it won't collide along with them.
+fn independent_key(state: &GameState) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |byte: u8| {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    };
+
+    for square in state.squares.iter() {
+        feed(match square {
+            Some(piece) => match piece.color {
+                Color::White => 1 + piece_index(piece.name) as u8,
+                Color::Black => 7 + piece_index(piece.name) as u8,
+            },
+            None => 0,
+        });
+    }
+    feed(match state.to_move {
+        Color::White => 0,
+        Color::Black => 1,
+    });
+    feed(state.white_can_castle_kingside as u8);
+    feed(state.white_can_castle_queenside as u8);
+    feed(state.black_can_castle_kingside as u8);
+    feed(state.black_can_castle_queenside as u8);
+    feed(state.en_passant_square.map_or(64, |square| square % 8) as u8);
+    hash
+}
+
+#[test]
+fn zobrist_key_test() {
+    let state = GameState::new();
+    let key = zobrist_key(&state);
+    assert_eq!(key, zobrist_key(&GameState::new()));
+
+    // Updating a key agrees with recomputing it
+    for next in legal_next_states(&state) {
+        assert_eq!(update_key(key, &state, &next), zobrist_key(&next));
+        assert_ne!(zobrist_key(&next), key);
+    }
+
+    // Including castling, en passant, and promotion with and without a capture
+    let special_moves = [
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1",
+        "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+    ];
+    for fen in special_moves.iter() {
+        let position = crate::environment::parse_fen(fen).unwrap().state;
+        let key = zobrist_key(&position);
+        for next in legal_next_states(&position) {
+            assert_eq!(update_key(key, &position, &next), zobrist_key(&next));
+        }
+    }
+
+    // Transpositions reach the same key
+    let line = |moves: &[&str]| {
+        moves.iter().fold(state, |state, notation| {
+            crate::notation::resolve_coordinate_move(&state, notation).unwrap()
+        })
+    };
+    let knights_first = line(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+    let queenside_first = line(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+    assert_eq!(zobrist_key(&knights_first), zobrist_key(&queenside_first));
+
+    let mut detector = CollisionDetector::new();
+    assert!(!detector.record(zobrist_key(&knights_first), &knights_first));
+    assert!(!detector.record(zobrist_key(&queenside_first), &queenside_first));
+    assert!(detector.record(zobrist_key(&knights_first), &state));
+    assert_eq!(detector.statistics(), CollisionStatistics { positions: 2, keys: 1, collisions: 1 });
+}